@@ -41,16 +41,16 @@ pub(crate) struct TagUser {
 }
 
 impl ApiClient {
-    pub(crate) fn new(config: &ApiConfig, party_id: PartyId) -> Self {
-        Self {
+    pub(crate) fn new(config: &ApiConfig, party_id: PartyId) -> Result<Self> {
+        Ok(Self {
             base_url: config.base_url.to_owned(),
-            client_token: config.client_token.to_owned(),
+            client_token: config.get_client_token()?,
             party_id,
             agent: build_agent(
                 Duration::from_secs(config.timeout_in_seconds),
                 !config.tls_verify,
             ),
-        }
+        })
     }
 
     pub(crate) fn sign_on(&self) -> Result<()> {
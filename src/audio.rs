@@ -3,65 +3,329 @@
  * License: MIT
  */
 
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::BufReader;
 use std::path::{Path, PathBuf};
 
 use anyhow::Result;
-use anyhow::ensure;
+use anyhow::{anyhow, ensure};
 use rodio::{Decoder, OutputStream, OutputStreamBuilder, Sink};
 
+use crate::config::{Config, SoundCandidate};
+use crate::random;
+use crate::soundpacks::{self, SoundPack, SoundPackName};
+use crate::tts::{Phrase, SpeechSynthesizer};
+
 pub(crate) struct SoundLibrary {
     path: PathBuf,
+    pack_name: Option<SoundPackName>,
+    pack_chain: Vec<SoundPack>,
 }
 
 impl SoundLibrary {
-    fn new(path: PathBuf) -> SoundLibrary {
-        SoundLibrary { path }
+    pub(crate) fn new(path: PathBuf, pack_name: Option<SoundPackName>) -> Result<SoundLibrary> {
+        let pack_chain = match &pack_name {
+            Some(pack_name) => soundpacks::load_sound_pack_chain(&path, pack_name)?,
+            None => vec![SoundPack::from_flat_directory(path.clone())],
+        };
+
+        if let Some(pack_name) = &pack_name {
+            log::info!("Using sound pack '{}'.", pack_name);
+        }
+
+        Ok(SoundLibrary {
+            path,
+            pack_name,
+            pack_chain,
+        })
+    }
+
+    /// Return the first sound pack that provides the sound, along with
+    /// the candidate files it provides for it.
+    fn find_candidates(&self, name: &str) -> Option<(&SoundPack, Vec<SoundCandidate>)> {
+        self.pack_chain.iter().find_map(|pack| {
+            let candidates = pack.find_candidates(name);
+            (!candidates.is_empty()).then_some((pack, candidates))
+        })
+    }
+
+    fn has_sound(&self, name: &str) -> bool {
+        self.find_candidates(name).is_some_and(|(_, candidates)| {
+            candidates
+                .iter()
+                .any(|candidate| Path::new(candidate.name()).exists())
+        })
+    }
+
+    /// Return the first sound pack that provides the sound, along with
+    /// all files it provides for it.
+    pub(crate) fn find_files(&self, name: &str) -> Option<(&SoundPackName, Vec<PathBuf>)> {
+        self.find_candidates(name).map(|(pack, candidates)| {
+            let paths = candidates
+                .iter()
+                .map(|candidate| PathBuf::from(candidate.name()))
+                .collect();
+            (&pack.name, paths)
+        })
+    }
+
+    pub(crate) fn get_conventional_path(&self, name: &str) -> PathBuf {
+        self.pack_chain[0].get_conventional_path(name)
     }
 
-    fn load_sound(&self, filename: &str) -> Result<Decoder<BufReader<File>>> {
-        let path = self.path.join(filename);
+    /// Load one of the existing files of the sound, chosen like a sound
+    /// variant.
+    fn load_sound(
+        &self,
+        name: &str,
+        sound_variants: &SoundVariants,
+    ) -> Result<Decoder<BufReader<File>>> {
+        let (pack, candidates) = self
+            .find_candidates(name)
+            .ok_or_else(|| anyhow!("Sound '{}' not found.", name))?;
+
+        let existing_candidates: Vec<SoundCandidate> = candidates
+            .iter()
+            .filter(|candidate| Path::new(candidate.name()).exists())
+            .cloned()
+            .collect();
         ensure!(
-            &path.exists(),
-            "Sound file {} does not exist.",
-            path.display()
+            !existing_candidates.is_empty(),
+            "Sound file(s) {} do not exist.",
+            candidates
+                .iter()
+                .map(SoundCandidate::name)
+                .collect::<Vec<_>>()
+                .join(", ")
         );
 
-        let source = load_source(&path)?;
+        // Remember the last choice per pack, separately from the
+        // sound's configured variants.
+        let key = format!("{}:{}", pack.name, name);
+        let path = sound_variants
+            .choose(&key, &existing_candidates)
+            .ok_or_else(|| anyhow!("No file of sound '{}' has a weight above zero.", name))?;
+
+        let source = load_source(Path::new(&path))?;
 
         Ok(source)
     }
+
+    fn switch_to_next_pack(&mut self) -> Result<SoundPackName> {
+        let current_name = self
+            .pack_name
+            .as_ref()
+            .ok_or_else(|| anyhow!("No sound pack configured."))?;
+
+        let names = soundpacks::list_sound_pack_names(&self.path)?;
+        let next_name = names
+            .iter()
+            .position(|name| name == current_name)
+            .and_then(|index| names.get(index + 1))
+            .or(names.first())
+            .ok_or_else(|| anyhow!("No sound packs found."))?
+            .to_owned();
+
+        self.pack_chain = soundpacks::load_sound_pack_chain(&self.path, &next_name)?;
+        self.pack_name = Some(next_name.clone());
+
+        Ok(next_name)
+    }
+}
+
+/// Chooses one of multiple configured variants of a sound, avoiding to
+/// choose the same variant twice in a row.
+pub(crate) struct SoundVariants {
+    variants: HashMap<String, Vec<SoundCandidate>>,
+    last_choices: RefCell<HashMap<String, String>>,
+}
+
+impl SoundVariants {
+    pub fn new(variants: HashMap<String, Vec<SoundCandidate>>) -> SoundVariants {
+        SoundVariants {
+            variants,
+            last_choices: RefCell::new(HashMap::new()),
+        }
+    }
+
+    pub fn get_variant_names(&self, name: &str) -> Option<Vec<&str>> {
+        self.variants
+            .get(name)
+            .map(|candidates| candidates.iter().map(SoundCandidate::name).collect())
+    }
+
+    /// Return the name of a variant of the sound, or the name itself if
+    /// it has no variants or none of them is available.
+    fn resolve<F>(&self, name: &str, is_available: F) -> String
+    where
+        F: Fn(&str) -> bool,
+    {
+        let available_candidates: Vec<SoundCandidate> = self
+            .variants
+            .get(name)
+            .into_iter()
+            .flatten()
+            .filter(|candidate| is_available(candidate.name()))
+            .cloned()
+            .collect();
+
+        self.choose(name, &available_candidates)
+            .unwrap_or_else(|| name.to_owned())
+    }
+
+    pub fn choose(&self, key: &str, candidates: &[SoundCandidate]) -> Option<String> {
+        let mut last_choices = self.last_choices.borrow_mut();
+        let last_choice = last_choices.get(key);
+
+        let fresh_candidates: Vec<SoundCandidate> = candidates
+            .iter()
+            .filter(|candidate| Some(candidate.name()) != last_choice.map(String::as_str))
+            .filter(|candidate| candidate.weight() > 0)
+            .cloned()
+            .collect();
+
+        let candidates = if fresh_candidates.is_empty() {
+            candidates
+        } else {
+            &fresh_candidates
+        };
+
+        let choice = random::choose_weighted_element(candidates, SoundCandidate::weight)
+            .map(|candidate| candidate.name().to_owned())?;
+
+        last_choices.insert(key.to_owned(), choice.clone());
+
+        Some(choice)
+    }
 }
 
 pub(crate) struct AudioPlayer {
-    sound_lib: SoundLibrary,
+    sound_lib: RefCell<SoundLibrary>,
+    sound_variants: SoundVariants,
+    speech_synthesizer: Option<SpeechSynthesizer>,
     _output_stream: OutputStream, // Hold reference to keep sound playback working!
     sink: Sink,
 }
 
 impl AudioPlayer {
-    pub fn new(sounds_path: PathBuf) -> Result<AudioPlayer> {
-        let sound_lib = SoundLibrary::new(sounds_path);
+    pub fn new(
+        sounds_path: PathBuf,
+        sound_pack_name: Option<SoundPackName>,
+        sound_variants: SoundVariants,
+        speech_synthesizer: Option<SpeechSynthesizer>,
+    ) -> Result<AudioPlayer> {
+        let sound_lib = RefCell::new(SoundLibrary::new(sounds_path, sound_pack_name)?);
 
         let output_stream = OutputStreamBuilder::open_default_stream()?;
         let sink = Sink::connect_new(output_stream.mixer());
 
         Ok(AudioPlayer {
             sound_lib,
+            sound_variants,
+            speech_synthesizer,
             _output_stream: output_stream,
             sink,
         })
     }
 
+    pub fn has_sound(&self, name: &str) -> bool {
+        let sound_lib = self.sound_lib.borrow();
+        match self.sound_variants.variants.get(name) {
+            Some(candidates) => candidates
+                .iter()
+                .any(|candidate| sound_lib.has_sound(candidate.name())),
+            None => sound_lib.has_sound(name),
+        }
+    }
+
+    pub fn can_speak(&self) -> bool {
+        self.speech_synthesizer.is_some()
+    }
+
+    pub fn choose_variant(&self, key: &str, candidates: &[SoundCandidate]) -> Option<String> {
+        self.sound_variants.choose(key, candidates)
+    }
+
     pub fn play(&self, name: &str) -> Result<()> {
-        let filename = format!("{}.ogg", name);
-        let source = self.sound_lib.load_sound(&filename)?;
-        self.sink.append(source);
-        self.sink.sleep_until_end();
+        let name = {
+            let sound_lib = self.sound_lib.borrow();
+            self.sound_variants
+                .resolve(name, |variant| sound_lib.has_sound(variant))
+        };
+        let source = self
+            .sound_lib
+            .borrow()
+            .load_sound(&name, &self.sound_variants)?;
+        self.append_and_wait(source);
 
         Ok(())
     }
+
+    pub fn switch_to_next_sound_pack(&self) -> Result<SoundPackName> {
+        self.sound_lib.borrow_mut().switch_to_next_pack()
+    }
+
+    /// Play the named sound, or speak the phrase instead if the sound
+    /// file is missing and text-to-speech is configured. If speaking
+    /// fails (e.g. because the command timed out), play the fallback
+    /// sound.
+    pub fn play_or_speak(
+        &self,
+        name: &str,
+        phrase: Option<Phrase>,
+        fallback_name: &str,
+    ) -> Result<()> {
+        if !self.has_sound(name)
+            && self.can_speak()
+            && let Some(phrase) = phrase
+        {
+            log::debug!("Sound '{}' is missing, speaking instead.", name);
+            return self.speak(&phrase).or_else(|e| {
+                log::warn!("Could not speak, playing sound '{fallback_name}' instead: {e}");
+                self.play(fallback_name)
+            });
+        }
+
+        self.play(name)
+    }
+
+    pub fn speak(&self, phrase: &Phrase) -> Result<()> {
+        let speech_synthesizer = self
+            .speech_synthesizer
+            .as_ref()
+            .ok_or_else(|| anyhow!("Text-to-speech is not configured."))?;
+
+        let text = speech_synthesizer.render(phrase);
+        log::debug!("Speaking \"{}\" ...", text);
+
+        let path = speech_synthesizer.synthesize(&text)?;
+        let source = load_source(&path)?;
+        self.append_and_wait(source);
+
+        Ok(())
+    }
+
+    fn append_and_wait(&self, source: Decoder<BufReader<File>>) {
+        self.sink.append(source);
+        self.sink.sleep_until_end();
+    }
+}
+
+pub(crate) fn create_audio_player(config: &Config) -> Result<AudioPlayer> {
+    let speech_synthesizer = config
+        .tts
+        .as_ref()
+        .map(SpeechSynthesizer::new)
+        .transpose()?;
+
+    AudioPlayer::new(
+        config.sounds_path.clone(),
+        config.sound_pack.clone(),
+        SoundVariants::new(config.sound_variants.clone()),
+        speech_synthesizer,
+    )
 }
 
 fn load_source(path: &Path) -> Result<Decoder<BufReader<File>>> {
@@ -72,31 +336,132 @@ fn load_source(path: &Path) -> Result<Decoder<BufReader<File>>> {
 pub(crate) enum Sound {
     AdminModeEntered,
     AdminModeLeft,
+    AdminSoundPackSwitched,
     SignOnSucceeded,
     SignOnFailed,
     SignOffSucceeded,
     SignOffFailed,
     UserTagCustomGreeting(String),
     UserTagUnknown,
+    UserGreeting,
+    UserName(String),
     WhereaboutsStatusUpdated,
     WhereaboutsStatusUpdatedCustom(String),
     CommunicationFailed,
 }
 
 impl Sound {
+    /// Return all sounds that do not depend on configuration or API data.
+    pub fn get_fixed_sounds() -> Vec<Sound> {
+        vec![
+            Sound::AdminModeEntered,
+            Sound::AdminModeLeft,
+            Sound::AdminSoundPackSwitched,
+            Sound::SignOnSucceeded,
+            Sound::SignOnFailed,
+            Sound::SignOffSucceeded,
+            Sound::SignOffFailed,
+            Sound::UserTagUnknown,
+            Sound::UserGreeting,
+            Sound::WhereaboutsStatusUpdated,
+            Sound::CommunicationFailed,
+        ]
+    }
+
     pub fn get_name(&self) -> String {
         match self {
             Sound::AdminModeEntered => "admin_mode_entered".to_owned(),
             Sound::AdminModeLeft => "admin_mode_left".to_owned(),
+            Sound::AdminSoundPackSwitched => "sound_pack_switched".to_owned(),
             Sound::SignOnSucceeded => "sign_on_succeeded".to_owned(),
             Sound::SignOnFailed => "sign_on_failed".to_owned(),
             Sound::SignOffSucceeded => "sign_off_succeeded".to_owned(),
             Sound::SignOffFailed => "sign_off_failed".to_owned(),
             Sound::UserTagCustomGreeting(name) => name.to_owned(),
             Sound::UserTagUnknown => "user_tag_unknown".to_owned(),
+            Sound::UserGreeting => "user_greeting".to_owned(),
+            Sound::UserName(screen_name) => format!("user_names/{}", screen_name),
             Sound::WhereaboutsStatusUpdated => "whereabouts_status_updated".to_owned(),
             Sound::WhereaboutsStatusUpdatedCustom(name) => name.to_owned(),
             Sound::CommunicationFailed => "communication_failed".to_owned(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(name: &str) -> SoundCandidate {
+        SoundCandidate::Name(name.to_string())
+    }
+
+    fn weighted(name: &str, weight: u32) -> SoundCandidate {
+        SoundCandidate::Weighted {
+            name: name.to_string(),
+            weight,
+        }
+    }
+
+    fn create_variants(name: &str, candidates: Vec<SoundCandidate>) -> SoundVariants {
+        SoundVariants::new(HashMap::from([(name.to_string(), candidates)]))
+    }
+
+    #[test]
+    fn single_variant_is_chosen_repeatedly() {
+        let variants = SoundVariants::new(HashMap::new());
+        let candidates = [name("moin")];
+
+        for _ in 0..3 {
+            assert_eq!(
+                variants.choose("greeting", &candidates),
+                Some("moin".to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn variants_without_weight_are_not_chosen() {
+        let variants = SoundVariants::new(HashMap::new());
+        let candidates = [weighted("moin", 0), name("ahoi")];
+
+        for _ in 0..10 {
+            assert_eq!(
+                variants.choose("greeting", &candidates),
+                Some("ahoi".to_string())
+            );
+        }
+
+        assert_eq!(variants.choose("other", &[weighted("moin", 0)]), None);
+    }
+
+    #[test]
+    fn same_variant_is_not_chosen_twice_in_a_row() {
+        let variants = SoundVariants::new(HashMap::new());
+        let candidates = [weighted("moin", 100), name("ahoi")];
+
+        let mut last_choice = variants.choose("greeting", &candidates).unwrap();
+        for _ in 0..20 {
+            let choice = variants.choose("greeting", &candidates).unwrap();
+            assert_ne!(choice, last_choice);
+            last_choice = choice;
+        }
+    }
+
+    #[test]
+    fn unavailable_variants_are_skipped() {
+        let variants = create_variants("greeting", vec![name("moin"), name("ahoi")]);
+
+        for _ in 0..10 {
+            assert_eq!(variants.resolve("greeting", |name| name == "ahoi"), "ahoi");
+        }
+    }
+
+    #[test]
+    fn sound_itself_is_used_if_no_variant_is_available() {
+        let variants = create_variants("greeting", vec![name("moin")]);
+
+        assert_eq!(variants.resolve("greeting", |_| false), "greeting");
+        assert_eq!(variants.resolve("farewell", |_| true), "farewell");
+    }
+}
@@ -0,0 +1,291 @@
+/*
+ * Copyright 2022-2026 Jochen Kupperschmidt
+ * License: MIT
+ */
+
+use std::collections::HashMap;
+
+use anyhow::{Context, Result, anyhow};
+use evdev::{AbsInfo, AbsoluteAxisCode, KeyCode};
+
+use crate::config::AxisConfig;
+use crate::keycodenames::KeyCodeNameMapping;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Direction {
+    Negative,
+    Positive,
+}
+
+impl Direction {
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Self::Negative => "negative",
+            Self::Positive => "positive",
+        }
+    }
+}
+
+struct AxisMapping {
+    negative_threshold: i32,
+    positive_threshold: i32,
+    negative_key_code: Option<KeyCode>,
+    positive_key_code: Option<KeyCode>,
+}
+
+impl AxisMapping {
+    fn get_direction(&self, value: i32) -> Option<Direction> {
+        if value <= self.negative_threshold {
+            Some(Direction::Negative)
+        } else if value >= self.positive_threshold {
+            Some(Direction::Positive)
+        } else {
+            None
+        }
+    }
+
+    fn get_key_code(&self, direction: Direction) -> Option<KeyCode> {
+        match direction {
+            Direction::Negative => self.negative_key_code,
+            Direction::Positive => self.positive_key_code,
+        }
+    }
+}
+
+/// Translates movements of absolute axes (e.g. a d-pad reported as
+/// `ABS_HAT0X`/`ABS_HAT0Y`) into presses and releases of virtual keys.
+///
+/// An axis counts as pushed into a direction while its value is beyond
+/// the respective threshold.
+pub(crate) struct AxisTranslator {
+    mappings: HashMap<AbsoluteAxisCode, AxisMapping>,
+    directions: HashMap<AbsoluteAxisCode, Direction>,
+}
+
+impl AxisTranslator {
+    pub(crate) fn new(
+        axis_configs: &[AxisConfig],
+        key_code_name_mapping: &KeyCodeNameMapping,
+    ) -> Result<Self> {
+        let find_key_code = |key_name: &Option<String>| -> Result<Option<KeyCode>> {
+            key_name
+                .as_ref()
+                .map(|key_name| {
+                    key_code_name_mapping
+                        .find_code_for_name(key_name.clone())
+                        .with_context(|| format!("Unknown axis key name '{}'", key_name))
+                })
+                .transpose()
+        };
+
+        let mut mappings = HashMap::new();
+        for axis_config in axis_configs {
+            let axis = parse_axis_name(&axis_config.axis)?;
+            let mapping = AxisMapping {
+                negative_threshold: axis_config.negative_threshold,
+                positive_threshold: axis_config.positive_threshold,
+                negative_key_code: find_key_code(&axis_config.negative)?,
+                positive_key_code: find_key_code(&axis_config.positive)?,
+            };
+            mappings.insert(axis, mapping);
+        }
+
+        Ok(Self::with_mappings(mappings))
+    }
+
+    /// Create a translator without virtual keys that uses thresholds
+    /// halfway between the center and the ends of each axis' range, to
+    /// detect movements of axes whose configuration is not yet known.
+    pub(crate) fn for_axis_ranges(
+        axis_infos: impl IntoIterator<Item = (AbsoluteAxisCode, AbsInfo)>,
+    ) -> Self {
+        let mappings = axis_infos
+            .into_iter()
+            .map(|(axis, info)| {
+                let (negative_threshold, positive_threshold) =
+                    get_default_thresholds(info.minimum(), info.maximum());
+                let mapping = AxisMapping {
+                    negative_threshold,
+                    positive_threshold,
+                    negative_key_code: None,
+                    positive_key_code: None,
+                };
+                (axis, mapping)
+            })
+            .collect();
+
+        Self::with_mappings(mappings)
+    }
+
+    fn with_mappings(mappings: HashMap<AbsoluteAxisCode, AxisMapping>) -> Self {
+        Self {
+            mappings,
+            directions: HashMap::new(),
+        }
+    }
+
+    /// Return the virtual keys released (with `false`) and pressed (with
+    /// `true`) by the axis movement.
+    pub(crate) fn translate(&mut self, axis: AbsoluteAxisCode, value: i32) -> Vec<(KeyCode, bool)> {
+        let Some((previous_direction, direction)) = self.change_direction(axis, value) else {
+            return Vec::new();
+        };
+        let mapping = &self.mappings[&axis];
+
+        let released = previous_direction
+            .and_then(|direction| mapping.get_key_code(direction))
+            .map(|key_code| (key_code, false));
+        let pressed = direction
+            .and_then(|direction| mapping.get_key_code(direction))
+            .map(|key_code| (key_code, true));
+
+        released.into_iter().chain(pressed).collect()
+    }
+
+    /// Return the direction the axis has been pushed into by the
+    /// movement, if any.
+    pub(crate) fn detect_push(&mut self, axis: AbsoluteAxisCode, value: i32) -> Option<Direction> {
+        self.change_direction(axis, value)
+            .and_then(|(_, direction)| direction)
+    }
+
+    /// Track the axis' direction, and return the previous and the new
+    /// one if the movement changed it.
+    fn change_direction(
+        &mut self,
+        axis: AbsoluteAxisCode,
+        value: i32,
+    ) -> Option<(Option<Direction>, Option<Direction>)> {
+        let mapping = self.mappings.get(&axis)?;
+
+        let previous_direction = self.directions.get(&axis).copied();
+        let direction = mapping.get_direction(value);
+        if direction == previous_direction {
+            return None;
+        }
+
+        match direction {
+            Some(direction) => self.directions.insert(axis, direction),
+            None => self.directions.remove(&axis),
+        };
+
+        Some((previous_direction, direction))
+    }
+}
+
+/// Return thresholds halfway between the center and the ends of the
+/// range (e.g. -1 and 1 for a d-pad reporting -1 to 1).
+fn get_default_thresholds(minimum: i32, maximum: i32) -> (i32, i32) {
+    let (minimum, maximum) = (i64::from(minimum), i64::from(maximum));
+    let center = (minimum + maximum).div_euclid(2);
+
+    // Round away from the center so that the center itself never counts
+    // as pushed. Without room on one side (e.g. a trigger reporting 0 to
+    // 1), put the threshold out of reach.
+    let negative_threshold = if center > minimum {
+        center - (center - minimum + 1) / 2
+    } else {
+        minimum - 1
+    };
+    let positive_threshold = if maximum > center {
+        center + (maximum - center + 1) / 2
+    } else {
+        maximum + 1
+    };
+
+    (
+        clamp_to_i32(negative_threshold),
+        clamp_to_i32(positive_threshold),
+    )
+}
+
+fn clamp_to_i32(value: i64) -> i32 {
+    value.clamp(i64::from(i32::MIN), i64::from(i32::MAX)) as i32
+}
+
+/// Parse an axis name like `hat0x` (for `ABS_HAT0X`).
+fn parse_axis_name(name: &str) -> Result<AbsoluteAxisCode> {
+    format!("ABS_{}", name.to_uppercase())
+        .parse()
+        .map_err(|_| anyhow!("Unknown axis name '{}'", name))
+}
+
+/// Return the name of an axis like `hat0x` (for `ABS_HAT0X`).
+pub(crate) fn format_axis_name(axis: AbsoluteAxisCode) -> String {
+    let name = format!("{:?}", axis);
+    name.strip_prefix("ABS_").unwrap_or(&name).to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AXIS: AbsoluteAxisCode = AbsoluteAxisCode::ABS_HAT0X;
+
+    fn create_translator() -> AxisTranslator {
+        let axis_config = AxisConfig {
+            axis: "hat0x".to_string(),
+            negative_threshold: -50,
+            positive_threshold: 50,
+            negative: Some("code:600".to_string()),
+            positive: Some("code:601".to_string()),
+        };
+
+        AxisTranslator::new(&[axis_config], &KeyCodeNameMapping::new().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn key_is_pressed_at_threshold_and_released_below_it() {
+        let mut translator = create_translator();
+        let positive_key = KeyCode::new(601);
+
+        assert_eq!(translator.translate(AXIS, 49), Vec::new());
+        assert_eq!(translator.translate(AXIS, 50), vec![(positive_key, true)]);
+        assert_eq!(translator.translate(AXIS, 100), Vec::new());
+        assert_eq!(translator.translate(AXIS, 49), vec![(positive_key, false)]);
+        assert_eq!(translator.translate(AXIS, 0), Vec::new());
+    }
+
+    #[test]
+    fn switching_direction_releases_before_pressing() {
+        let mut translator = create_translator();
+        let (negative_key, positive_key) = (KeyCode::new(600), KeyCode::new(601));
+
+        assert_eq!(translator.translate(AXIS, -50), vec![(negative_key, true)]);
+        assert_eq!(
+            translator.translate(AXIS, 50),
+            vec![(negative_key, false), (positive_key, true)]
+        );
+        assert_eq!(translator.translate(AXIS, 0), vec![(positive_key, false)]);
+    }
+
+    #[test]
+    fn movements_of_unmapped_axes_are_ignored() {
+        let mut translator = create_translator();
+
+        assert_eq!(
+            translator.translate(AbsoluteAxisCode::ABS_HAT0Y, 100),
+            Vec::new()
+        );
+    }
+
+    #[test]
+    fn push_is_detected_once_per_threshold_crossing() {
+        let axis_info = AbsInfo::new(128, 0, 255, 0, 0, 0);
+        let mut translator = AxisTranslator::for_axis_ranges([(AXIS, axis_info)]);
+
+        assert_eq!(translator.detect_push(AXIS, 127), None);
+        assert_eq!(translator.detect_push(AXIS, 200), Some(Direction::Positive));
+        assert_eq!(translator.detect_push(AXIS, 255), None);
+        assert_eq!(translator.detect_push(AXIS, 127), None);
+        assert_eq!(translator.detect_push(AXIS, 0), Some(Direction::Negative));
+    }
+
+    #[test]
+    fn default_thresholds_lie_between_center_and_ends() {
+        assert_eq!(get_default_thresholds(-1, 1), (-1, 1));
+        assert_eq!(get_default_thresholds(0, 255), (63, 191));
+        assert_eq!(get_default_thresholds(-32768, 32767), (-16385, 16383));
+        assert_eq!(get_default_thresholds(0, 1), (-1, 1));
+    }
+}
@@ -4,28 +4,58 @@
  */
 
 use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard};
 use std::thread;
+use std::time::Instant;
 
-use anyhow::{Context, Result};
-use evdev::{Device, EventSummary, KeyCode};
+use anyhow::{Context, Error, Result, anyhow, bail, ensure};
+use evdev::{AbsoluteAxisCode, Device, EventSummary, KeyCode};
 use serde::Deserialize;
 
+use crate::axes::{self, AxisTranslator};
+use crate::config::{Config, GpioConfig};
+use crate::debounce::Debouncer;
 use crate::devices;
 use crate::devices::DeviceName;
 use crate::events::EventSender;
-use crate::keycodenames::{KeyCodeNameMapping, KeyName};
+use crate::gestures::{self, ButtonEdge, ButtonEdgeSender, GestureDetector};
+use crate::gpio::{InputLines, LineOffset};
+use crate::keycodenames::{self, KeyCodeNameMapping, KeyName};
+use crate::status::Status;
 
-pub(crate) fn identify_buttons(device_name: DeviceName) -> Result<()> {
+pub(crate) fn identify_buttons(device_name: DeviceName, grab: bool) -> Result<()> {
     let key_code_name_mapping = KeyCodeNameMapping::new()?;
 
-    let device = open_device(device_name)?;
+    let device = open_device(device_name, grab)?;
+
+    // Report axis movements only once they cross a threshold, not on
+    // every value change (of which analog axes produce plenty).
+    let mut axis_translator = AxisTranslator::for_axis_ranges(device.get_absinfo()?);
 
     println!("\nPress buttons now. Ctrl-C to exit.");
 
-    handle_key_presses(device, |code| {
+    handle_input_events(device, |input_change| {
+        let code = match input_change {
+            InputChange::Key(code, KeyState::Pressed) => code,
+            InputChange::Axis(axis, value) => {
+                if let Some(direction) = axis_translator.detect_push(axis, value) {
+                    println!(
+                        "Axis movement detected. Axis name: '{}'. Direction: {}. Value: {}.",
+                        axes::format_axis_name(axis),
+                        direction.name(),
+                        value
+                    );
+                }
+                return Ok(());
+            }
+            _ => return Ok(()),
+        };
+
         let name_repr = match key_code_name_mapping.find_name_for_code(code) {
             Some(name) => format!("'{}'", name),
-            None => "not assigned".to_owned(),
+            None => format!("'{}'", keycodenames::format_raw_code(code)),
         };
 
         println!(
@@ -38,21 +68,113 @@ pub(crate) fn identify_buttons(device_name: DeviceName) -> Result<()> {
 }
 
 pub(crate) fn handle_button_presses(
-    device_name: DeviceName,
-    buttons_to_key_code_names: HashMap<Button, KeyName>,
+    config: &Config,
     event_sender: EventSender,
-) -> Result<()> {
-    let key_code_name_mapping = KeyCodeNameMapping::new()?;
+    status: &Status,
+) -> Result<Arc<ButtonDiagnostics>> {
+    let buttons_config = &config.buttons;
+
+    let device_configs = config.get_button_input_devices();
+
+    ensure!(
+        !device_configs.is_empty() || config.gpio.is_some(),
+        "Neither a button input device nor GPIO buttons are configured."
+    );
+
+    let (edge_sender, edge_receiver) = gestures::create_button_edge_channel();
+    let (debounced_edge_sender, debounced_edge_receiver) = gestures::create_button_edge_channel();
+
+    let diagnostics = status.metrics().get_button_diagnostics();
+
+    for device_config in device_configs {
+        let key_code_name_mapping = KeyCodeNameMapping::new()?;
+
+        let axis_translator = AxisTranslator::new(&buttons_config.axes, &key_code_name_mapping)?;
+
+        let key_codes_to_buttons = KeyCodeToButtonMapping::new(
+            key_code_name_mapping,
+            device_config.buttons_to_key_code_names,
+        )?;
+
+        let device = open_device(device_config.device.clone(), device_config.grab)?;
+
+        let mut button_handler = ButtonHandler::new(
+            key_codes_to_buttons,
+            axis_translator,
+            EdgeForwarder::new(
+                device_config.device.clone(),
+                diagnostics.clone(),
+                edge_sender.clone(),
+            ),
+        );
+
+        let status = status.clone();
+        thread::spawn(move || {
+            status.track_device(device_config.device, "button input device", || {
+                handle_input_events(device, |input_change| {
+                    button_handler.handle_input_change(input_change)
+                })
+            })
+        });
+    }
 
-    let key_codes_to_buttons =
-        KeyCodeToButtonMapping::new(key_code_name_mapping, buttons_to_key_code_names)?;
+    if let Some(gpio_config) = &config.gpio {
+        let edge_forwarder = EdgeForwarder::new(
+            gpio_config.chip.display().to_string(),
+            diagnostics.clone(),
+            edge_sender.clone(),
+        );
 
-    let device = open_device(device_name)?;
+        handle_gpio_button_presses(gpio_config, edge_forwarder, status.clone())?;
+    }
 
-    let button_handler = ButtonHandler::new(key_codes_to_buttons, event_sender);
+    let gesture_detector = GestureDetector::new(buttons_config, event_sender);
 
+    let debouncer = Debouncer::new(buttons_config);
+    let debouncer_diagnostics = diagnostics.clone();
     thread::spawn(move || {
-        handle_key_presses(device, |key_code| button_handler.handle_key_code(key_code))
+        debouncer.run(edge_receiver, debounced_edge_sender, debouncer_diagnostics)
+    });
+
+    thread::spawn(move || gesture_detector.run(debounced_edge_receiver));
+
+    Ok(diagnostics)
+}
+
+fn handle_gpio_button_presses(
+    gpio_config: &GpioConfig,
+    mut edge_forwarder: EdgeForwarder,
+    status: Status,
+) -> Result<()> {
+    let lines_to_buttons: HashMap<LineOffset, Button> = gpio_config
+        .buttons_to_lines
+        .iter()
+        .map(|(button, offset)| (*offset, button.clone()))
+        .collect();
+    let offsets: Vec<LineOffset> = lines_to_buttons.keys().copied().collect();
+
+    let mut lines = InputLines::request(
+        &gpio_config.chip,
+        &offsets,
+        gpio_config.bias,
+        gpio_config.active_low,
+    )?;
+    log::info!(
+        "Requested GPIO lines {:?} from {}.",
+        offsets,
+        gpio_config.chip.display()
+    );
+
+    let chip_name = gpio_config.chip.display().to_string();
+    thread::spawn(move || {
+        status.track_device(chip_name, "GPIO chip", || {
+            loop {
+                let edge = lines.read_edge()?;
+                if let Some(button) = lines_to_buttons.get(&edge.offset) {
+                    edge_forwarder.forward(button.clone(), edge.active)?;
+                }
+            }
+        })
     });
 
     Ok(())
@@ -69,12 +191,23 @@ impl KeyCodeToButtonMapping {
     ) -> Result<Self> {
         let mut key_codes_to_buttons: HashMap<KeyCode, Button> = HashMap::new();
 
+        let mut buttons_to_key_code_names: Vec<(Button, KeyName)> =
+            buttons_to_key_code_names.into_iter().collect();
+        buttons_to_key_code_names.sort();
+
         for (button, key_name) in buttons_to_key_code_names {
             let key_code = key_code_name_mapping
                 .find_code_for_name(key_name.clone())
                 .with_context(|| format!("Unknown button key name '{}'", key_name))?;
 
-            key_codes_to_buttons.insert(*key_code, button);
+            if let Some(other_button) = key_codes_to_buttons.insert(key_code, button.clone()) {
+                bail!(
+                    "Buttons {} and {} are both mapped to key '{}'.",
+                    other_button,
+                    button,
+                    key_name
+                );
+            }
         }
 
         Ok(Self {
@@ -87,54 +220,302 @@ impl KeyCodeToButtonMapping {
     }
 }
 
-fn open_device(device_name: DeviceName) -> Result<Device> {
+pub(crate) fn open_device(device_name: DeviceName, grab: bool) -> Result<Device> {
     let device_label = "button input device".to_string();
-    devices::open_input_device(device_name, device_label)
+    devices::open_input_device(device_name, device_label, grab)
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct ButtonCounters {
+    pub presses: u64,
+    pub repeats_ignored: u64,
+    pub bounces_ignored: u64,
+}
+
+/// Per-button counters of accepted and ignored input, to help with
+/// diagnosing faulty buttons
+#[derive(Default)]
+pub(crate) struct ButtonDiagnostics {
+    counters: Mutex<HashMap<Button, ButtonCounters>>,
+}
+
+impl ButtonDiagnostics {
+    pub(crate) fn count<F>(&self, button: &Button, increment: F)
+    where
+        F: FnOnce(&mut ButtonCounters),
+    {
+        let mut counters = self.lock();
+        increment(counters.entry(button.clone()).or_default());
+    }
+
+    pub(crate) fn get_counters(&self) -> HashMap<Button, ButtonCounters> {
+        self.lock().clone()
+    }
+
+    pub(crate) fn log_summary(&self) {
+        for (button, counters) in self.get_counters() {
+            log::info!(
+                "Button {}: {} presses, {} key repeats ignored, {} bounces ignored",
+                button,
+                counters.presses,
+                counters.repeats_ignored,
+                counters.bounces_ignored
+            );
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<Button, ButtonCounters>> {
+        // Counters stay usable even if a thread panicked while holding
+        // the lock.
+        self.counters.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Passes presses and releases of buttons on to debouncing (and then
+/// gesture detection).
+struct EdgeForwarder {
+    source: DeviceName,
+    diagnostics: Arc<ButtonDiagnostics>,
+    edge_sender: ButtonEdgeSender,
+}
+
+impl EdgeForwarder {
+    fn new(
+        source: DeviceName,
+        diagnostics: Arc<ButtonDiagnostics>,
+        edge_sender: ButtonEdgeSender,
+    ) -> Self {
+        Self {
+            source,
+            diagnostics,
+            edge_sender,
+        }
+    }
+
+    fn forward(&mut self, button: Button, pressed: bool) -> Result<()> {
+        self.edge_sender.send(ButtonEdge {
+            button,
+            pressed,
+            time: Instant::now(),
+            source: self.source.clone(),
+        })?;
+
+        Ok(())
+    }
+
+    fn ignore_repeat(&self, button: &Button) {
+        self.diagnostics
+            .count(button, |counters| counters.repeats_ignored += 1);
+    }
 }
 
 struct ButtonHandler {
     key_codes_to_buttons: KeyCodeToButtonMapping,
-    event_sender: EventSender,
+    axis_translator: AxisTranslator,
+    edge_forwarder: EdgeForwarder,
 }
 
 impl ButtonHandler {
-    fn new(key_codes_to_buttons: KeyCodeToButtonMapping, event_sender: EventSender) -> Self {
+    fn new(
+        key_codes_to_buttons: KeyCodeToButtonMapping,
+        axis_translator: AxisTranslator,
+        edge_forwarder: EdgeForwarder,
+    ) -> Self {
         Self {
             key_codes_to_buttons,
-            event_sender,
+            axis_translator,
+            edge_forwarder,
         }
     }
 
-    fn handle_key_code(&self, key_code: KeyCode) -> Result<()> {
-        if let Some(button) = self.key_codes_to_buttons.find_button_for_key_code(key_code) {
-            self.event_sender.send_button_pressed(button)?;
+    fn handle_input_change(&mut self, input_change: InputChange) -> Result<()> {
+        match input_change {
+            InputChange::Key(key_code, key_state) => self.handle_key_event(key_code, key_state),
+            InputChange::Axis(axis, value) => {
+                for (key_code, pressed) in self.axis_translator.translate(axis, value) {
+                    let key_state = if pressed {
+                        KeyState::Pressed
+                    } else {
+                        KeyState::Released
+                    };
+                    self.handle_key_event(key_code, key_state)?;
+                }
+                Ok(())
+            }
         }
-        Ok(())
     }
+
+    fn handle_key_event(&mut self, key_code: KeyCode, key_state: KeyState) -> Result<()> {
+        let Some(button) = self.key_codes_to_buttons.find_button_for_key_code(key_code) else {
+            return Ok(());
+        };
+
+        match key_state {
+            KeyState::Pressed => self.edge_forwarder.forward(button, true),
+            KeyState::Released => self.edge_forwarder.forward(button, false),
+            KeyState::Repeated => {
+                // Auto-repeat of a held key, not an actual press
+                self.edge_forwarder.ignore_repeat(&button);
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum KeyState {
+    Released,
+    Pressed,
+    Repeated,
 }
 
-fn handle_key_presses<F>(mut device: Device, handle_key_code: F) -> Result<()>
+pub(crate) enum InputChange {
+    Key(KeyCode, KeyState),
+    Axis(AbsoluteAxisCode, i32),
+}
+
+/// Call the handler for each key press, release, and repeat, as well as
+/// for each movement of an absolute axis.
+pub(crate) fn handle_input_events<F>(mut device: Device, mut handle_input_change: F) -> Result<()>
 where
-    F: Fn(KeyCode) -> Result<()>,
+    F: FnMut(InputChange) -> Result<()>,
 {
     loop {
         for event in device.fetch_events()? {
-            if let EventSummary::Key(_, key_code, 1) = event.destructure() {
-                handle_key_code(key_code)?
-            }
+            let input_change = match event.destructure() {
+                EventSummary::Key(_, key_code, value) => {
+                    let key_state = match value {
+                        0 => KeyState::Released,
+                        1 => KeyState::Pressed,
+                        2 => KeyState::Repeated,
+                        _ => continue,
+                    };
+                    InputChange::Key(key_code, key_state)
+                }
+                EventSummary::AbsoluteAxis(_, axis, value) => InputChange::Axis(axis, value),
+                _ => continue,
+            };
+            handle_input_change(input_change)?
         }
     }
 }
 
+/// A button, identified by a name chosen in the configuration (e.g.
+/// `green` or `button1`)
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[serde(try_from = "String")]
+pub(crate) struct Button(String);
+
+impl TryFrom<String> for Button {
+    type Error = Error;
+
+    fn try_from(name: String) -> Result<Self> {
+        ensure!(!name.is_empty(), "Button name must not be empty");
+        ensure!(
+            !name.contains(':'),
+            "Button name '{}' must not contain a colon",
+            name
+        );
+        Ok(Self(name))
+    }
+}
+
+impl FromStr for Button {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::try_from(s.to_owned())
+    }
+}
+
+impl fmt::Display for Button {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub(crate) enum Gesture {
+    Press,
+    LongPress,
+    DoublePress,
+}
+
+/// A gesture performed with a button
+///
+/// Written as the button name for a short press, or the button name
+/// followed by `:long` or `:double` (e.g. `button1:long`).
 #[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq)]
-#[serde(rename_all = "snake_case")]
-pub(crate) enum Button {
-    Button1,
-    Button2,
-    Button3,
-    Button4,
-    Button5,
-    Button6,
-    Button7,
-    Button8,
+#[serde(try_from = "String")]
+pub(crate) struct ButtonGesture {
+    pub button: Button,
+    pub gesture: Gesture,
+}
+
+impl ButtonGesture {
+    pub(crate) fn new(button: Button, gesture: Gesture) -> Self {
+        Self { button, gesture }
+    }
+}
+
+impl TryFrom<String> for ButtonGesture {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        let (button_name, gesture) = match value.split_once(':') {
+            None => (value.as_str(), Gesture::Press),
+            Some((button_name, "long")) => (button_name, Gesture::LongPress),
+            Some((button_name, "double")) => (button_name, Gesture::DoublePress),
+            Some((_, suffix)) => return Err(anyhow!("Unknown button gesture '{}'", suffix)),
+        };
+
+        Ok(Self::new(button_name.parse()?, gesture))
+    }
+}
+
+impl fmt::Display for ButtonGesture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.gesture {
+            Gesture::Press => write!(f, "{}", self.button),
+            Gesture::LongPress => write!(f, "{} (long)", self.button),
+            Gesture::DoublePress => write!(f, "{} (double)", self.button),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_mapping(entries: &[(&str, &str)]) -> Result<KeyCodeToButtonMapping> {
+        let buttons_to_key_code_names = entries
+            .iter()
+            .map(|(button, key_name)| (Button::from_str(button).unwrap(), key_name.to_string()))
+            .collect();
+
+        KeyCodeToButtonMapping::new(KeyCodeNameMapping::new()?, buttons_to_key_code_names)
+    }
+
+    #[test]
+    fn buttons_are_found_by_key_code() {
+        let mapping = create_mapping(&[("blue", "tl"), ("red", "key_a")]).unwrap();
+
+        assert_eq!(
+            mapping.find_button_for_key_code(KeyCode::BTN_TL),
+            Some(Button::from_str("blue").unwrap())
+        );
+        assert_eq!(mapping.find_button_for_key_code(KeyCode::BTN_TR), None);
+    }
+
+    #[test]
+    fn buttons_mapped_to_the_same_key_are_rejected() {
+        let error = create_mapping(&[("button1", "tl"), ("button5", "tl")])
+            .err()
+            .expect("duplicate key is rejected");
+
+        assert_eq!(
+            error.to_string(),
+            "Buttons button1 and button5 are both mapped to key 'tl'."
+        );
+    }
 }
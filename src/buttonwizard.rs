@@ -0,0 +1,204 @@
+/*
+ * Copyright 2022-2026 Jochen Kupperschmidt
+ * License: MIT
+ */
+
+//! Interactive mapping of buttons to keys and whereabouts, written to
+//! the configuration file
+
+use std::collections::HashMap;
+use std::io::{Write, stdin, stdout};
+use std::path::Path;
+use std::str::FromStr;
+use std::thread;
+
+use anyhow::{Context, Result, bail};
+use toml_edit::{DocumentMut, value};
+
+use crate::buttons::{self, Button, InputChange, KeyState};
+use crate::configedit::{self, get_or_insert_table};
+use crate::devices::DeviceName;
+use crate::keycodenames::{self, KeyCodeNameMapping, KeyName};
+
+const KEY_MAPPING_TABLE: &str = "buttons_to_key_codes";
+
+pub(crate) fn map_buttons(
+    device_name: DeviceName,
+    config_filename: &Path,
+    button_count: Option<u8>,
+    grab: bool,
+) -> Result<()> {
+    let mut document = configedit::load_document(config_filename)?;
+
+    let buttons = determine_buttons(&document, button_count)?;
+    if buttons.is_empty() {
+        bail!(
+            "No buttons configured in {}, specify the number of buttons.",
+            config_filename.display()
+        );
+    }
+
+    let key_code_name_mapping = KeyCodeNameMapping::new()?;
+
+    let device = buttons::open_device(device_name.clone(), grab)?;
+
+    let (key_sender, key_receiver) = flume::unbounded();
+    thread::spawn(move || {
+        buttons::handle_input_events(device, |input_change| {
+            if let InputChange::Key(code, KeyState::Pressed) = input_change {
+                key_sender.send(code)?;
+            }
+            Ok(())
+        })
+    });
+
+    // Keys of buttons that are not mapped (again) stay in use.
+    let mut keys_to_buttons = get_keys_of_other_buttons(&document, &buttons);
+
+    for button in &buttons {
+        println!("\nPress the button for {}.", button);
+
+        // Ignore presses made before being asked.
+        key_receiver.drain();
+
+        let key_name = loop {
+            let code = key_receiver
+                .recv()
+                .context("Reading from button input device failed")?;
+            let key_name = key_code_name_mapping
+                .find_name_for_code(code)
+                .cloned()
+                .unwrap_or_else(|| keycodenames::format_raw_code(code));
+
+            match keys_to_buttons.get(&key_name) {
+                Some(other) => println!(
+                    "Key '{}' is already mapped to {}. Press another button.",
+                    key_name, other
+                ),
+                None => break key_name,
+            }
+        };
+        println!("Detected key '{}'.", key_name);
+        keys_to_buttons.insert(key_name.clone(), button.clone());
+
+        get_or_insert_table(document.as_table_mut(), KEY_MAPPING_TABLE)?
+            .insert(&button.to_string(), value(key_name));
+
+        let current_whereabouts = document
+            .get("party")
+            .and_then(|party| party.get("buttons_to_whereabouts"))
+            .and_then(|whereabouts| whereabouts.get(button.to_string()))
+            .and_then(|whereabouts| whereabouts.as_str())
+            .map(|whereabouts| whereabouts.to_string());
+
+        let question = match &current_whereabouts {
+            Some(whereabouts) => format!(
+                "Whereabouts for {} (leave empty to keep '{}'): ",
+                button, whereabouts
+            ),
+            None => format!("Whereabouts for {} (leave empty to skip): ", button),
+        };
+        let whereabouts = ask(&question)?;
+        if !whereabouts.is_empty() {
+            let party = get_or_insert_table(document.as_table_mut(), "party")?;
+            get_or_insert_table(party, "buttons_to_whereabouts")?
+                .insert(&button.to_string(), value(whereabouts));
+        }
+    }
+
+    if document.get("button_input_device").is_none()
+        && document.get("button_input_devices").is_none()
+    {
+        document["button_input_device"] = value(device_name);
+    }
+
+    configedit::write_document(config_filename, &document)?;
+
+    println!(
+        "\nWrote mapping of {} buttons to {}.",
+        buttons.len(),
+        config_filename.display()
+    );
+
+    Ok(())
+}
+
+/// Return buttons `button1` to `buttonN` if a number of buttons is
+/// given, or else the buttons already present in the key mapping.
+fn determine_buttons(document: &DocumentMut, button_count: Option<u8>) -> Result<Vec<Button>> {
+    if let Some(button_count) = button_count {
+        return (1..=button_count)
+            .map(|number| Button::from_str(&format!("button{}", number)))
+            .collect();
+    }
+
+    let Some(table) = document
+        .get(KEY_MAPPING_TABLE)
+        .and_then(|item| item.as_table_like())
+    else {
+        return Ok(Vec::new());
+    };
+
+    table
+        .iter()
+        .map(|(name, _)| Button::from_str(name))
+        .collect()
+}
+
+/// Return the keys mapped to buttons other than the given ones in the
+/// key mapping.
+fn get_keys_of_other_buttons(
+    document: &DocumentMut,
+    buttons: &[Button],
+) -> HashMap<KeyName, Button> {
+    let Some(table) = document
+        .get(KEY_MAPPING_TABLE)
+        .and_then(|item| item.as_table_like())
+    else {
+        return HashMap::new();
+    };
+
+    table
+        .iter()
+        .filter_map(|(name, item)| {
+            let button = Button::from_str(name).ok()?;
+            let key_name = item.as_str()?;
+            (!buttons.contains(&button)).then(|| (key_name.to_string(), button))
+        })
+        .collect()
+}
+
+fn ask(question: &str) -> Result<String> {
+    print!("{}", question);
+    stdout().flush()?;
+
+    let mut answer = String::new();
+    stdin().read_line(&mut answer)?;
+
+    Ok(answer.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_of_buttons_not_mapped_again_stay_in_use() {
+        let document: DocumentMut = r#"
+            [buttons_to_key_codes]
+            button1 = "tl"
+            button5 = "tr"
+        "#
+        .parse()
+        .unwrap();
+        let buttons = determine_buttons(&document, Some(2)).unwrap();
+
+        let keys_to_buttons = get_keys_of_other_buttons(&document, &buttons);
+
+        assert_eq!(keys_to_buttons.len(), 1);
+        assert_eq!(
+            keys_to_buttons.get("tr"),
+            Some(&Button::from_str("button5").unwrap())
+        );
+    }
+}
@@ -7,33 +7,75 @@ use std::path::PathBuf;
 
 use clap::{Parser, Subcommand};
 
+use crate::config::ConfigOverride;
+
 /// Command-line arguments
 #[derive(Parser, Debug)]
 #[clap(about, author, version)]
 pub(crate) struct Cli {
     #[command(subcommand)]
     pub command: Command,
+
+    /// Override a configuration value (e.g. `party.party_id=lanparty`);
+    /// can be given multiple times
+    #[clap(long = "set", value_name = "KEY=VALUE", global = true)]
+    pub overrides: Vec<ConfigOverride>,
 }
 
 #[derive(Debug, Subcommand)]
 pub(crate) enum Command {
-    /// Detect button presses and display corresponding key code names
+    /// Detect button presses and display corresponding key code names,
+    /// or interactively map buttons and write them to a configuration
+    /// file
     IdentifyButtons {
         /// Specify button input device (e.g.
-        /// `/dev/input/by-id/usb-RANDOM-GAMEPAD-event-joystick`)
+        /// `/dev/input/by-id/usb-RANDOM-GAMEPAD-event-joystick` or
+        /// `usb=046d:c216`)
         #[clap(short = 'd', long = "device")]
         device: String,
+
+        /// Walk through the buttons and write their mapping to this
+        /// configuration file (e.g. `config.toml`)
+        #[clap(short = 'c', long = "config")]
+        config_filename: Option<PathBuf>,
+
+        /// Map this number of buttons (named `button1` etc.) instead of
+        /// those already in the configuration file
+        #[clap(long = "button-count", requires = "config_filename")]
+        button_count: Option<u8>,
+
+        /// Do not request exclusive access to the device (e.g. to run
+        /// alongside `evtest`)
+        #[clap(long = "no-grab")]
+        no_grab: bool,
     },
 
+    /// List input devices with their properties and capabilities
+    ListDevices,
+
     /// Register a Verbleiber client
     Register {
         /// Specify API hots
-        #[clap(long = "base-url")]
-        base_url: String,
+        #[clap(long = "base-url", required_unless_present = "resume")]
+        base_url: Option<String>,
 
         /// Supply number of buttons
-        #[clap(long = "button-count")]
-        button_count: u8,
+        #[clap(
+            long = "button-count",
+            required_unless_present_any = ["config_filename", "resume"]
+        )]
+        button_count: Option<u8>,
+
+        /// Take number of buttons from configuration file (e.g.
+        /// `config.toml`)
+        #[clap(short = 'c', long = "config")]
+        config_filename: Option<PathBuf>,
+
+        /// Write API settings including the client token to the
+        /// configuration file (created from the example configuration if
+        /// it does not exist)
+        #[clap(long = "write-config", requires = "config_filename")]
+        write_config: bool,
 
         /// Specify if device has audio output
         #[clap(long = "audio-output")]
@@ -42,6 +84,63 @@ pub(crate) enum Command {
         /// Disable TLS verification
         #[clap(long = "no-tls-verify")]
         disable_tls_verification: bool,
+
+        /// Describe where the client is set up (e.g. "main entrance"),
+        /// to tell clients apart when approving them
+        #[clap(long = "location")]
+        location: Option<String>,
+
+        /// Report this hostname instead of the system's
+        #[clap(long = "hostname")]
+        hostname: Option<String>,
+
+        /// Report this MAC address instead of the detected one
+        #[clap(long = "mac-address")]
+        mac_address: Option<String>,
+
+        /// Report type of tag reader (e.g. `rfid`, `barcode`); can be
+        /// given multiple times
+        #[clap(long = "reader-type")]
+        reader_types: Vec<String>,
+
+        /// Report button name (instead of those from the configuration
+        /// file); can be given multiple times
+        #[clap(long = "button-name")]
+        button_names: Vec<String>,
+
+        /// Resume waiting for approval of a pending registration
+        #[clap(
+            long = "resume",
+            conflicts_with_all = [
+                "base_url",
+                "button_count",
+                "audio_output",
+                "disable_tls_verification",
+                "location",
+                "hostname",
+                "mac_address",
+                "reader_types",
+                "button_names",
+            ]
+        )]
+        resume: bool,
+
+        /// Specify file to keep a pending registration in
+        #[clap(long = "state", default_value = "verbleiber-registration.toml")]
+        state_filename: PathBuf,
+
+        /// Seconds to wait between checks for approval
+        #[clap(
+            long = "poll-interval",
+            default_value_t = 10,
+            value_parser = clap::value_parser!(u64).range(1..)
+        )]
+        poll_interval_in_seconds: u64,
+
+        /// Give up waiting for approval after this many seconds (the
+        /// registration can be resumed later)
+        #[clap(long = "timeout")]
+        timeout_in_seconds: Option<u64>,
     },
 
     /// Run the Verbleiber client
@@ -50,6 +149,31 @@ pub(crate) enum Command {
         #[clap(short = 'c', long = "config")]
         config_filename: PathBuf,
     },
+
+    /// Play a sound as the client would (to test the audio setup)
+    PlaySound {
+        /// Specify configuration filename (e.g. `config.toml`)
+        #[clap(short = 'c', long = "config")]
+        config_filename: PathBuf,
+
+        /// Name of the sound (e.g. `sign_on_succeeded`)
+        name: String,
+    },
+
+    /// List all sounds the configuration can refer to, and their files
+    ListSounds {
+        /// Specify configuration filename (e.g. `config.toml`)
+        #[clap(short = 'c', long = "config")]
+        config_filename: PathBuf,
+    },
+
+    /// Show the effective configuration, including overrides, with
+    /// secrets masked
+    ShowConfig {
+        /// Specify configuration filename (e.g. `config.toml`)
+        #[clap(short = 'c', long = "config")]
+        config_filename: PathBuf,
+    },
 }
 
 pub(crate) fn parse_cli() -> Cli {
@@ -3,18 +3,20 @@
  * License: MIT
  */
 
-use std::collections::HashSet;
-use std::path::PathBuf;
+use std::time::Instant;
 
 use anyhow::Result;
 
 use crate::api::ApiClient;
 use crate::audio::{AudioPlayer, Sound};
-use crate::buttons::Button;
-use crate::config::{ApiConfig, PartyConfig};
+use crate::buttons::ButtonGesture;
+use crate::config::{Config, GreetingStyle, PartyConfig};
 use crate::events::{Event, EventReceiver, EventSender};
-use crate::model::{CurrentUser, Tag, UserId, UserMode};
-use crate::random;
+use crate::lights::{Lights, LightsMode};
+use crate::metrics::{ApiEndpoint, TagReadResult};
+use crate::model::{AdminAction, AdminSettings, CurrentUser, Tag, UserId, UserMode};
+use crate::status::Status;
+use crate::tts::Phrase;
 
 enum EventHandlingResult {
     KeepCurrentUser,
@@ -25,8 +27,10 @@ enum EventHandlingResult {
 
 pub(crate) struct Client {
     audio_player: AudioPlayer,
+    lights: Lights,
+    status: Status,
     user_mode: UserMode,
-    admin_tags: HashSet<Tag>,
+    admin_settings: AdminSettings,
     api_client: ApiClient,
     party_config: PartyConfig,
     event_receiver: EventReceiver,
@@ -35,20 +39,22 @@ pub(crate) struct Client {
 
 impl Client {
     pub(crate) fn new(
-        sounds_path: PathBuf,
+        audio_player: AudioPlayer,
+        lights: Lights,
+        status: Status,
         user_mode: UserMode,
-        admin_tags: HashSet<Tag>,
-        api_config: &ApiConfig,
-        party_config: PartyConfig,
+        config: Config,
         event_receiver: EventReceiver,
         event_sender: EventSender,
     ) -> Result<Self> {
         Ok(Self {
-            audio_player: AudioPlayer::new(sounds_path)?,
+            audio_player,
+            lights,
+            status,
             user_mode,
-            admin_tags,
-            api_client: ApiClient::new(api_config, party_config.party_id.clone()),
-            party_config,
+            admin_settings: config.get_admin_settings(),
+            api_client: ApiClient::new(&config.api, config.party.party_id.clone())?,
+            party_config: config.party,
             event_receiver,
             event_sender,
         })
@@ -69,6 +75,7 @@ impl Client {
         };
 
         let mut current_user = default_current_user.clone();
+        self.show_current_user(&current_user);
 
         for event in self.event_receiver.iter() {
             let result = match self.user_mode {
@@ -84,12 +91,23 @@ impl Client {
                 EventHandlingResult::Abort => {
                     break;
                 }
-            }
+            };
+            self.show_current_user(&current_user);
         }
 
         Ok(())
     }
 
+    fn show_current_user(&self, current_user: &CurrentUser) {
+        let mode = match current_user {
+            CurrentUser::None => LightsMode::Idle,
+            CurrentUser::User(_) => LightsMode::AwaitingWhereabouts,
+            CurrentUser::Admin => LightsMode::AdminMode,
+        };
+        self.lights.set_mode(mode);
+        self.status.set_current_user(current_user);
+    }
+
     fn handle_single_user_event(
         &self,
         event: Event,
@@ -100,10 +118,21 @@ impl Client {
                 log::error!("Unexpected tag read event received.");
                 EventHandlingResult::ResetCurrentUser
             }
-            Event::ButtonPressed { button } => {
-                log::debug!("Button pressed: {:?}", button);
-                self.handle_button_press_with_identified_user(&single_user_id, button)?
+            Event::ButtonPressed {
+                button_gesture,
+                source,
+            } => {
+                log::debug!("Button pressed: {} (on {})", button_gesture, source);
+                self.status.metrics().count_button_press(&button_gesture);
+                self.handle_button_press_with_identified_user(&single_user_id, button_gesture)?
             }
+            Event::ChordActivated { action } => match action {
+                AdminAction::EnterAdminMode | AdminAction::LeaveAdminMode => {
+                    log::warn!("Admin mode is not available in single-user mode.");
+                    EventHandlingResult::KeepCurrentUser
+                }
+                _ => self.perform_admin_action(action)?,
+            },
             Event::ShutdownRequested => {
                 self.shutdown()?;
                 EventHandlingResult::Abort
@@ -119,19 +148,28 @@ impl Client {
         Ok(match event {
             Event::TagRead { tag } => {
                 log::debug!("Tag read: {}", tag.value);
+                self.status.record_tag_read();
                 self.handle_tag_read(&tag)?
             }
-            Event::ButtonPressed { button } => {
-                log::debug!("Button pressed: {:?}", button);
+            Event::ButtonPressed {
+                button_gesture,
+                source,
+            } => {
+                log::debug!("Button pressed: {} (on {})", button_gesture, source);
+                self.status.metrics().count_button_press(&button_gesture);
 
                 match current_user {
-                    CurrentUser::Admin => self.handle_button_press_by_admin(button)?,
+                    CurrentUser::Admin => self.handle_button_press_by_admin(button_gesture)?,
                     CurrentUser::User(user_id) => {
-                        self.handle_button_press_with_identified_user(user_id, button)?
+                        self.handle_button_press_with_identified_user(user_id, button_gesture)?
                     }
                     CurrentUser::None => EventHandlingResult::ResetCurrentUser,
                 }
             }
+            Event::ChordActivated { action } => {
+                log::debug!("Chord activated: {:?}", action);
+                self.perform_admin_action(action)?
+            }
             _ => self.handle_common_event(event)?,
         })
     }
@@ -151,9 +189,11 @@ impl Client {
 
     fn sign_on(&self) -> Result<()> {
         log::info!("Signing on ...");
-        match self.api_client.sign_on() {
+        let result = self.call_api(ApiEndpoint::SignOn, |api_client| api_client.sign_on());
+        match result {
             Ok(()) => {
                 log::info!("Signed on.");
+                self.status.set_signed_on(true);
                 self.play_sound(Sound::SignOnSucceeded);
             }
             Err(e) => {
@@ -166,9 +206,11 @@ impl Client {
 
     fn sign_off(&self) -> Result<()> {
         log::info!("Signing off ...");
-        match self.api_client.sign_off() {
+        let result = self.call_api(ApiEndpoint::SignOff, |api_client| api_client.sign_off());
+        match result {
             Ok(()) => {
                 log::info!("Signed off.");
+                self.status.set_signed_on(false);
                 self.play_sound(Sound::SignOffSucceeded);
             }
             Err(e) => {
@@ -180,27 +222,33 @@ impl Client {
     }
 
     fn handle_tag_read(&self, tag: &Tag) -> Result<EventHandlingResult> {
-        if self.admin_tags.contains(tag) {
-            self.play_sound(Sound::AdminModeEntered);
-            log::info!("Entering admin mode.");
-            return Ok(EventHandlingResult::SetCurrentUser(CurrentUser::Admin));
+        if self.admin_settings.tags.contains(tag) {
+            self.status.metrics().count_tag_read(TagReadResult::Known);
+            return self.perform_admin_action(AdminAction::EnterAdminMode);
         }
 
         log::debug!("Requesting details for tag {} ...", tag.value);
-        match self.api_client.get_tag_details(tag) {
+        let result = self.call_api(ApiEndpoint::TagDetails, |api_client| {
+            api_client.get_tag_details(tag)
+        });
+        let tag_read_result = match &result {
+            Ok(Some(_)) => TagReadResult::Known,
+            Ok(None) => TagReadResult::Unknown,
+            Err(_) => TagReadResult::Error,
+        };
+        self.status.metrics().count_tag_read(tag_read_result);
+        match result {
             Ok(details) => match details {
                 Some(details) => {
                     log::debug!(
                         "User for tag {}: {} (ID: {})",
                         details.identifier,
-                        details.user.screen_name.unwrap_or("<nameless>".to_string()),
+                        details.user.screen_name.as_deref().unwrap_or("<nameless>"),
                         details.user.id
                     );
                     let user_id = details.user.id;
 
-                    if let Some(name) = details.sound_name {
-                        self.play_sound(Sound::UserTagCustomGreeting(name));
-                    }
+                    self.greet_user(details.sound_name, details.user.screen_name);
 
                     log::debug!("Awaiting whereabouts for user {user_id} ...");
 
@@ -224,20 +272,85 @@ impl Client {
         }
     }
 
-    fn handle_button_press_by_admin(&self, button: Button) -> Result<EventHandlingResult> {
-        Ok(match button {
-            Button::Button1 => {
-                // Leave admin mode.
+    fn greet_user(&self, sound_name: Option<String>, screen_name: Option<String>) {
+        match self.party_config.greeting_style {
+            GreetingStyle::TagSound => {
+                if let Some(name) = sound_name {
+                    let phrase = screen_name.map(|screen_name| Phrase::Greeting { screen_name });
+                    self.play_sound_or_speak(
+                        Sound::UserTagCustomGreeting(name),
+                        phrase,
+                        Sound::UserGreeting,
+                    );
+                }
+            }
+            GreetingStyle::NameSounds => {
+                self.play_sound(Sound::UserGreeting);
+
+                if let Some(screen_name) = screen_name.filter(|name| is_safe_sound_name(name)) {
+                    let sound = Sound::UserName(screen_name);
+                    if self.audio_player.has_sound(&sound.get_name()) {
+                        self.play_sound(sound);
+                    } else {
+                        log::debug!("No name sound for user, skipping.");
+                    }
+                }
+            }
+            GreetingStyle::Speech => match screen_name {
+                Some(screen_name) if self.audio_player.can_speak() => {
+                    if let Err(e) = self.audio_player.speak(&Phrase::Greeting { screen_name }) {
+                        log::warn!("Could not speak greeting: {e}");
+                        self.play_sound(Sound::UserGreeting);
+                    }
+                }
+                _ => self.play_sound(Sound::UserGreeting),
+            },
+        }
+    }
+
+    fn handle_button_press_by_admin(
+        &self,
+        button_gesture: ButtonGesture,
+    ) -> Result<EventHandlingResult> {
+        let action = self
+            .admin_settings
+            .buttons_to_actions
+            .get(&button_gesture)
+            .copied();
+
+        match action {
+            Some(action) => self.perform_admin_action(action),
+            None => {
+                // Stay in admin mode.
+                Ok(EventHandlingResult::KeepCurrentUser)
+            }
+        }
+    }
+
+    fn perform_admin_action(&self, action: AdminAction) -> Result<EventHandlingResult> {
+        Ok(match action {
+            AdminAction::EnterAdminMode => {
+                self.play_sound(Sound::AdminModeEntered);
+                log::info!("Entering admin mode.");
+                EventHandlingResult::SetCurrentUser(CurrentUser::Admin)
+            }
+            AdminAction::LeaveAdminMode => {
                 log::info!("Leaving admin mode.");
                 self.play_sound(Sound::AdminModeLeft);
                 EventHandlingResult::ResetCurrentUser
             }
-            Button::Button2 => {
+            AdminAction::ShutDown => {
                 self.event_sender.send_shutdown_requested()?;
                 EventHandlingResult::ResetCurrentUser
             }
-            _ => {
-                // Stay in admin mode.
+            AdminAction::SwitchSoundPack => {
+                match self.audio_player.switch_to_next_sound_pack() {
+                    Ok(name) => {
+                        log::info!("Switched to sound pack '{name}'.");
+                        self.play_sound(Sound::AdminSoundPackSwitched);
+                    }
+                    Err(e) => log::warn!("Could not switch sound pack: {e}"),
+                }
                 EventHandlingResult::KeepCurrentUser
             }
         })
@@ -246,12 +359,19 @@ impl Client {
     fn handle_button_press_with_identified_user(
         &self,
         user_id: &UserId,
-        button: Button,
+        button_gesture: ButtonGesture,
     ) -> Result<EventHandlingResult> {
-        if let Some(whereabouts_name) = &self.party_config.buttons_to_whereabouts.get(&button) {
+        if let Some(whereabouts_name) = &self
+            .party_config
+            .buttons_to_whereabouts
+            .get(&button_gesture)
+        {
             log::debug!("Updating whereabouts status for user {user_id} -> {whereabouts_name} ...");
 
             let response = self.update_status(user_id, whereabouts_name);
+            self.status
+                .metrics()
+                .count_status_update(whereabouts_name, response.is_ok());
             match response {
                 Ok(_) => {
                     log::debug!("Whereabouts status successfully updated.");
@@ -260,10 +380,16 @@ impl Client {
                         .party_config
                         .whereabouts_sounds
                         .get(*whereabouts_name)
-                        .and_then(|sound_names| random::choose_random_element(sound_names))
+                        .and_then(|candidates| {
+                            self.audio_player
+                                .choose_variant(whereabouts_name, candidates)
+                        })
                         .map(Sound::WhereaboutsStatusUpdatedCustom)
                         .unwrap_or(Sound::WhereaboutsStatusUpdated);
-                    self.play_sound(sound);
+                    let phrase = Phrase::Whereabouts {
+                        whereabouts_name: whereabouts_name.to_string(),
+                    };
+                    self.play_sound_or_speak(sound, Some(phrase), Sound::WhereaboutsStatusUpdated);
                 }
                 Err(e) => {
                     log::warn!("Whereabouts status update failed.\n{e}");
@@ -277,19 +403,61 @@ impl Client {
 
     fn shutdown(&self) -> Result<()> {
         log::info!("Shutdown requested.");
+        self.lights.set_mode(LightsMode::Off);
         self.sign_off()?;
         log::info!("Shutting down ...");
         Ok(())
     }
 
     fn update_status(&self, user_id: &UserId, whereabouts_name: &str) -> Result<()> {
-        self.api_client.update_status(user_id, whereabouts_name)
+        self.call_api(ApiEndpoint::StatusUpdate, |api_client| {
+            api_client.update_status(user_id, whereabouts_name)
+        })
+    }
+
+    /// Call the API, measuring how long that takes, showing whether the
+    /// API is reachable, and keeping the error (if any).
+    fn call_api<T, F>(&self, endpoint: ApiEndpoint, call: F) -> Result<T>
+    where
+        F: FnOnce(&ApiClient) -> Result<T>,
+    {
+        let started_at = Instant::now();
+        let result = call(&self.api_client);
+        self.status
+            .metrics()
+            .observe_api_latency(endpoint, started_at.elapsed());
+
+        self.lights.set_online(result.is_ok());
+        if let Err(e) = &result {
+            self.status.record_api_error(e);
+        }
+
+        result
     }
 
     fn play_sound(&self, sound: Sound) {
         let name = sound.get_name();
         if let Err(e) = self.audio_player.play(&name) {
             log::warn!("Could not play sound: {e}");
+            self.status.metrics().count_sound_playback_failure();
         }
     }
+
+    fn play_sound_or_speak(&self, sound: Sound, phrase: Option<Phrase>, fallback_sound: Sound) {
+        let name = sound.get_name();
+        let fallback_name = fallback_sound.get_name();
+        if let Err(e) = self
+            .audio_player
+            .play_or_speak(&name, phrase, &fallback_name)
+        {
+            log::warn!("Could not play sound: {e}");
+            self.status.metrics().count_sound_playback_failure();
+        }
+    }
+}
+
+/// Screen names become part of a path, so reject anything that could
+/// escape the sounds directory.
+fn is_safe_sound_name(name: &str) -> bool {
+    !name.is_empty() && !name.starts_with('.') && !name.contains(['/', '\\'])
 }
@@ -4,34 +4,263 @@
  */
 
 use std::collections::{HashMap, HashSet};
-use std::fs::read_to_string;
+use std::env;
+use std::fs::{canonicalize, metadata, read_to_string};
+use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
-use anyhow::Result;
+use anyhow::{Context, Result, anyhow, ensure};
 use serde::Deserialize;
 
-use crate::buttons::Button;
+use crate::buttons::{Button, ButtonGesture, Gesture};
+use crate::configschema::{self, ValueKind};
 use crate::devices::DeviceName;
+use crate::gpio::{Bias, LineOffset};
 use crate::keycodenames::KeyName;
-use crate::model::{PartyId, Tag, UserId, UserMode};
+use crate::model::{AdminAction, AdminSettings, PartyId, Tag, UserId, UserMode};
+use crate::soundpacks::SoundPackName;
 
 #[derive(Deserialize)]
 pub(crate) struct Config {
     pub reader_input_device: Option<DeviceName>,
-    pub button_input_device: DeviceName,
+    #[serde(default = "default_grab")]
+    pub grab_reader_input_device: bool,
+    pub button_input_device: Option<DeviceName>,
+    #[serde(default = "default_grab")]
+    pub grab_button_input_device: bool,
 
-    #[serde(rename = "buttons_to_key_codes")]
+    #[serde(rename = "buttons_to_key_codes", default)]
     pub buttons_to_key_code_names: HashMap<Button, KeyName>,
 
+    #[serde(default)]
+    pub button_input_devices: Vec<ButtonInputDeviceConfig>,
+
+    pub gpio: Option<GpioConfig>,
+
+    pub lights: Option<LightsConfig>,
+
+    #[serde(default)]
+    pub buttons: ButtonsConfig,
+
     pub sounds_path: PathBuf,
+    pub sound_pack: Option<SoundPackName>,
+    #[serde(default)]
+    pub sound_variants: HashMap<String, Vec<SoundCandidate>>,
+    pub tts: Option<TtsConfig>,
     pub api: ApiConfig,
     pub party: PartyConfig,
     pub admin: Option<AdminConfig>,
     pub single_user: Option<SingleUserConfig>,
+    pub status_server: Option<StatusServerConfig>,
 }
 
 impl Config {
-    pub fn get_admin_tags(&self) -> HashSet<Tag> {
+    /// Return all button input devices, including the one configured
+    /// via `button_input_device` (if any).
+    pub fn get_button_input_devices(&self) -> Vec<ButtonInputDeviceConfig> {
+        let single_device =
+            self.button_input_device
+                .clone()
+                .map(|device| ButtonInputDeviceConfig {
+                    device,
+                    grab: self.grab_button_input_device,
+                    buttons_to_key_code_names: self.buttons_to_key_code_names.clone(),
+                });
+
+        single_device
+            .into_iter()
+            .chain(self.button_input_devices.iter().cloned())
+            .collect()
+    }
+
+    /// Return the configured buttons, sorted by name.
+    pub fn get_buttons(&self) -> Vec<Button> {
+        let device_buttons = self
+            .get_button_input_devices()
+            .into_iter()
+            .flat_map(|device_config| device_config.buttons_to_key_code_names.into_keys());
+
+        let gpio_buttons = self
+            .gpio
+            .iter()
+            .flat_map(|gpio_config| gpio_config.buttons_to_lines.keys().cloned());
+
+        let mut buttons: Vec<Button> = device_buttons.chain(gpio_buttons).collect();
+        buttons.sort();
+        buttons.dedup();
+        buttons
+    }
+
+    /// Ensure that all buttons referred to are configured (mapped to a
+    /// key or GPIO line) to catch typos in button names.
+    fn validate_button_references(&self) -> Result<()> {
+        let buttons = self.get_buttons();
+
+        let mut references: Vec<(&str, &Button)> = Vec::new();
+        references.extend(
+            self.party
+                .buttons_to_whereabouts
+                .keys()
+                .map(|button_gesture| ("party.buttons_to_whereabouts", &button_gesture.button)),
+        );
+        if let Some(buttons_to_actions) = self
+            .admin
+            .as_ref()
+            .and_then(|admin_config| admin_config.buttons_to_actions.as_ref())
+        {
+            references.extend(
+                buttons_to_actions
+                    .keys()
+                    .map(|button_gesture| ("admin.buttons_to_actions", &button_gesture.button)),
+            );
+        }
+        references.extend(
+            self.buttons
+                .chords
+                .iter()
+                .flat_map(|chord_config| &chord_config.buttons)
+                .map(|button| ("buttons.chords", button)),
+        );
+        references.extend(
+            self.buttons
+                .debounce_in_ms_by_button
+                .keys()
+                .map(|button| ("buttons.debounce_in_ms_by_button", button)),
+        );
+        if let Some(lights_config) = &self.lights {
+            references.extend(
+                lights_config
+                    .buttons
+                    .keys()
+                    .map(|button| ("lights.buttons", button)),
+            );
+        }
+        references.sort();
+
+        for (section, button) in references {
+            ensure!(
+                buttons.contains(button),
+                "Unknown button '{}' in `{}`. Configured buttons: {}",
+                button,
+                section,
+                format_buttons(&buttons)
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Ensure that no GPIO line is mapped to multiple buttons, of which
+    /// only one would be reported.
+    fn validate_gpio_lines(&self) -> Result<()> {
+        let Some(gpio_config) = &self.gpio else {
+            return Ok(());
+        };
+
+        let mut lines_and_buttons: Vec<(LineOffset, &Button)> = gpio_config
+            .buttons_to_lines
+            .iter()
+            .map(|(button, offset)| (*offset, button))
+            .collect();
+        lines_and_buttons.sort();
+
+        for pair in lines_and_buttons.windows(2) {
+            let ((offset, button1), (other_offset, button2)) = (pair[0], pair[1]);
+            ensure!(
+                offset != other_offset,
+                "GPIO line {} is mapped to both buttons {} and {} in `gpio.buttons_to_lines`.",
+                offset,
+                button1,
+                button2
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Ensure that text-to-speech is configured if greetings are to be
+    /// spoken.
+    fn validate_greeting_style(&self) -> Result<()> {
+        ensure!(
+            !matches!(self.party.greeting_style, GreetingStyle::Speech) || self.tts.is_some(),
+            "Greeting style 'speech' requires section `tts` to be configured."
+        );
+
+        Ok(())
+    }
+
+    /// Ensure that each axis' thresholds leave a range in between, as
+    /// the axis could count as pushed into both directions otherwise.
+    fn validate_axis_thresholds(&self) -> Result<()> {
+        for axis_config in &self.buttons.axes {
+            ensure!(
+                axis_config.negative_threshold < axis_config.positive_threshold,
+                "Negative threshold ({}) of axis '{}' must be lower than its positive threshold ({}).",
+                axis_config.negative_threshold,
+                axis_config.axis,
+                axis_config.positive_threshold
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Warn if the default mapping of buttons to admin actions is in
+    /// effect but refers to buttons which are not configured (e.g.
+    /// because buttons have other names).
+    fn warn_about_missing_default_admin_buttons(&self) {
+        let is_admin_mapping_configured = self
+            .admin
+            .as_ref()
+            .is_some_and(|admin_config| admin_config.buttons_to_actions.is_some());
+        if is_admin_mapping_configured || !self.can_enter_admin_mode() {
+            return;
+        }
+
+        let buttons = self.get_buttons();
+        let mut missing_buttons: Vec<String> = self
+            .get_admin_buttons_to_actions()
+            .into_keys()
+            .map(|button_gesture| button_gesture.button)
+            .filter(|button| !buttons.contains(button))
+            .map(|button| button.to_string())
+            .collect();
+        if missing_buttons.is_empty() {
+            return;
+        }
+        missing_buttons.sort();
+
+        log::warn!(
+            "Default admin mode buttons {} are not configured, so admin mode cannot be left via buttons. Map buttons in `admin.buttons_to_actions`.",
+            missing_buttons.join(", ")
+        );
+    }
+
+    fn can_enter_admin_mode(&self) -> bool {
+        let has_admin_tags = self
+            .admin
+            .as_ref()
+            .and_then(|admin_config| admin_config.tags.as_ref())
+            .is_some_and(|tags| !tags.is_empty());
+
+        let has_admin_chord = self
+            .buttons
+            .chords
+            .iter()
+            .any(|chord_config| matches!(chord_config.action, AdminAction::EnterAdminMode));
+
+        has_admin_tags || has_admin_chord
+    }
+
+    pub fn get_admin_settings(&self) -> AdminSettings {
+        AdminSettings {
+            tags: self.get_admin_tags(),
+            buttons_to_actions: self.get_admin_buttons_to_actions(),
+        }
+    }
+
+    fn get_admin_tags(&self) -> HashSet<Tag> {
         self.admin
             .as_ref()
             .and_then(|admin_config| admin_config.tags.clone())
@@ -41,6 +270,19 @@ impl Config {
             .collect()
     }
 
+    fn get_admin_buttons_to_actions(&self) -> HashMap<ButtonGesture, AdminAction> {
+        self.admin
+            .as_ref()
+            .and_then(|admin_config| admin_config.buttons_to_actions.clone())
+            .unwrap_or_else(|| {
+                HashMap::from([
+                    (press_of("button1"), AdminAction::LeaveAdminMode),
+                    (press_of("button2"), AdminAction::ShutDown),
+                    (press_of("button3"), AdminAction::SwitchSoundPack),
+                ])
+            })
+    }
+
     pub fn get_user_mode(&self) -> UserMode {
         self.single_user
             .as_ref()
@@ -50,24 +292,259 @@ impl Config {
     }
 }
 
+fn format_buttons(buttons: &[Button]) -> String {
+    if buttons.is_empty() {
+        return "none".to_string();
+    }
+
+    buttons
+        .iter()
+        .map(|button| button.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn press_of(button_name: &str) -> ButtonGesture {
+    let button = button_name.parse().expect("Invalid default button name");
+    ButtonGesture::new(button, Gesture::Press)
+}
+
+#[derive(Default, Deserialize)]
+pub(crate) struct ButtonsConfig {
+    pub long_press_in_ms: Option<u64>,
+    pub double_press_window_in_ms: Option<u64>,
+    pub chord_window_in_ms: Option<u64>,
+    #[serde(default)]
+    pub chords: Vec<ChordConfig>,
+    #[serde(default)]
+    pub debounce_in_ms: u64,
+    #[serde(default)]
+    pub debounce_in_ms_by_button: HashMap<Button, u64>,
+    #[serde(default)]
+    pub axes: Vec<AxisConfig>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct AxisConfig {
+    pub axis: String,
+    pub negative_threshold: i32,
+    pub positive_threshold: i32,
+    pub negative: Option<KeyName>,
+    pub positive: Option<KeyName>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ChordConfig {
+    pub buttons: Vec<Button>,
+    #[serde(default)]
+    pub hold_in_ms: u64,
+    pub action: AdminAction,
+}
+
+#[derive(Clone, Deserialize)]
+pub(crate) struct ButtonInputDeviceConfig {
+    pub device: DeviceName,
+    #[serde(default = "default_grab")]
+    pub grab: bool,
+    #[serde(rename = "buttons_to_key_codes")]
+    pub buttons_to_key_code_names: HashMap<Button, KeyName>,
+}
+
+/// Request exclusive access to input devices unless configured
+/// otherwise.
+fn default_grab() -> bool {
+    true
+}
+
+#[derive(Deserialize)]
+pub(crate) struct GpioConfig {
+    pub chip: PathBuf,
+    #[serde(default)]
+    pub bias: Bias,
+    #[serde(default = "default_gpio_active_low")]
+    pub active_low: bool,
+    pub buttons_to_lines: HashMap<Button, LineOffset>,
+}
+
+fn default_gpio_active_low() -> bool {
+    true
+}
+
+#[derive(Deserialize)]
+pub(crate) struct LightsConfig {
+    pub blink_interval_in_ms: Option<u64>,
+    pub buttons: HashMap<Button, LightConfig>,
+}
+
+/// Where the light of a button is connected
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum LightConfig {
+    /// LED of an input device (e.g. `numl`)
+    Evdev { device: DeviceName, led: String },
+    /// GPIO output line
+    Gpio {
+        chip: PathBuf,
+        line: LineOffset,
+        #[serde(default)]
+        active_low: bool,
+    },
+    /// LED in `/sys/class/leds`
+    Sysfs { path: PathBuf },
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum SoundCandidate {
+    Name(String),
+    Weighted { name: String, weight: u32 },
+}
+
+impl SoundCandidate {
+    pub fn name(&self) -> &str {
+        match self {
+            SoundCandidate::Name(name) => name,
+            SoundCandidate::Weighted { name, .. } => name,
+        }
+    }
+
+    pub fn weight(&self) -> u32 {
+        match self {
+            SoundCandidate::Name(_) => 1,
+            SoundCandidate::Weighted { weight, .. } => *weight,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct TtsConfig {
+    pub command: Vec<String>,
+    #[serde(default = "default_tts_timeout_in_seconds")]
+    pub timeout_in_seconds: u64,
+    pub cache_path: PathBuf,
+    #[serde(default = "default_greeting_template")]
+    pub greeting_template: String,
+    #[serde(default = "default_whereabouts_template")]
+    pub whereabouts_template: String,
+    #[serde(default)]
+    pub whereabouts_labels: HashMap<String, String>,
+}
+
+fn default_tts_timeout_in_seconds() -> u64 {
+    10
+}
+
+fn default_greeting_template() -> String {
+    "Welcome, {screen_name}".to_string()
+}
+
+fn default_whereabouts_template() -> String {
+    "{whereabouts_label}".to_string()
+}
+
+/// Environment variable to take the client token from
+const CLIENT_TOKEN_ENV_VAR: &str = "VERBLEIBER_CLIENT_TOKEN";
+
+/// Name of the systemd credential to take the client token from
+const CLIENT_TOKEN_CREDENTIAL: &str = "client_token";
+
 #[derive(Deserialize)]
 pub(crate) struct ApiConfig {
     pub base_url: String,
-    pub client_token: String,
+    pub client_token: Option<String>,
+    pub client_token_file: Option<PathBuf>,
     pub tls_verify: bool,
     pub timeout_in_seconds: u64,
 }
 
+impl ApiConfig {
+    /// Return the client token, taken from the first of these sources
+    /// that is available:
+    ///
+    /// - environment variable `VERBLEIBER_CLIENT_TOKEN`
+    /// - systemd credential `client_token` (in `$CREDENTIALS_DIRECTORY`)
+    /// - file specified as `client_token_file`
+    /// - value of `client_token`
+    pub fn get_client_token(&self) -> Result<String> {
+        if let Ok(token) = env::var(CLIENT_TOKEN_ENV_VAR) {
+            log::debug!("Using client token from environment variable {CLIENT_TOKEN_ENV_VAR}.");
+            return Ok(token);
+        }
+
+        if let Some(credentials_path) = env::var_os("CREDENTIALS_DIRECTORY") {
+            let path = Path::new(&credentials_path).join(CLIENT_TOKEN_CREDENTIAL);
+            if path.exists() {
+                log::debug!("Using client token from systemd credential.");
+                return read_token_file(&path);
+            }
+        }
+
+        if let Some(path) = &self.client_token_file {
+            log::debug!("Using client token from {}.", path.display());
+            return read_token_file(path);
+        }
+
+        self.client_token.clone().ok_or_else(|| {
+            anyhow!(
+                "No client token configured (via `client_token`, `client_token_file`, environment variable {CLIENT_TOKEN_ENV_VAR}, or systemd credential `{CLIENT_TOKEN_CREDENTIAL}`)."
+            )
+        })
+    }
+}
+
+/// Read a client token from a file, which must not be accessible by
+/// anyone but its owner and group.
+fn read_token_file(path: &Path) -> Result<String> {
+    let metadata = metadata(path)
+        .with_context(|| format!("Could not access client token file {}", path.display()))?;
+
+    let mode = metadata.permissions().mode();
+    ensure!(
+        mode & 0o007 == 0,
+        "Client token file {} must not be accessible by others (mode {:o}). Restrict it via `chmod o-rwx`.",
+        path.display(),
+        mode & 0o777
+    );
+
+    let token = read_to_string(path)
+        .with_context(|| format!("Could not read client token file {}", path.display()))?
+        .trim()
+        .to_string();
+    ensure!(
+        !token.is_empty(),
+        "Client token file {} is empty.",
+        path.display()
+    );
+
+    Ok(token)
+}
+
 #[derive(Deserialize)]
 pub(crate) struct PartyConfig {
     pub party_id: PartyId,
-    pub buttons_to_whereabouts: HashMap<Button, String>,
-    pub whereabouts_sounds: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    pub greeting_style: GreetingStyle,
+    pub buttons_to_whereabouts: HashMap<ButtonGesture, String>,
+    pub whereabouts_sounds: HashMap<String, Vec<SoundCandidate>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum GreetingStyle {
+    /// Play the sound assigned to the tag by the API (if any).
+    #[default]
+    TagSound,
+    /// Play a generic greeting, followed by a per-user name sound (if
+    /// present).
+    NameSounds,
+    /// Speak a greeting including the user's screen name.
+    Speech,
 }
 
 #[derive(Deserialize)]
 pub(crate) struct AdminConfig {
     pub tags: Option<HashSet<String>>,
+    pub buttons_to_actions: Option<HashMap<ButtonGesture, AdminAction>>,
 }
 
 #[derive(Deserialize)]
@@ -75,8 +552,592 @@ pub(crate) struct SingleUserConfig {
     pub user_id: Option<UserId>,
 }
 
-pub(crate) fn load_config(path: &Path) -> Result<Config> {
-    let text = read_to_string(path)?;
-    let config: Config = toml::from_str(&text)?;
+#[derive(Deserialize)]
+pub(crate) struct StatusServerConfig {
+    /// Address (and port) to listen on
+    #[serde(default = "default_status_server_address")]
+    pub address: String,
+}
+
+fn default_status_server_address() -> String {
+    "127.0.0.1:9473".to_string()
+}
+
+/// Prefix of environment variables that override configuration values
+const OVERRIDE_ENV_VAR_PREFIX: &str = "VERBLEIBER_";
+
+/// Separator of section and property names in names of environment
+/// variables that override configuration values (as property names
+/// contain single underscores)
+const OVERRIDE_ENV_VAR_SEPARATOR: &str = "__";
+
+/// Property to list configuration files to include
+const INCLUDE_KEY: &str = "include";
+
+/// Properties whose values are masked when showing the configuration
+const SECRET_KEYS: &[&str] = &["client_token"];
+
+const MASKED_VALUE: &str = "********";
+
+/// A value replacing the one of a (possibly nested) configuration
+/// property, specified as `key=value` with dotted keys (e.g.
+/// `party.party_id=lanparty-2026`)
+#[derive(Clone, Debug)]
+pub(crate) struct ConfigOverride {
+    keys: Vec<String>,
+    value: toml::Value,
+}
+
+impl FromStr for ConfigOverride {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (key, value) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Expected 'key=value', got '{}'", s))?;
+
+        Self::new(key.trim().split('.'), value.trim())
+    }
+}
+
+impl ConfigOverride {
+    fn new<'a>(keys: impl Iterator<Item = &'a str>, value: &str) -> Result<Self> {
+        let keys: Vec<String> = keys.map(|key| key.to_string()).collect();
+        ensure!(
+            keys.iter().all(|key| !key.is_empty()),
+            "Invalid configuration key '{}'",
+            keys.join(".")
+        );
+        ensure!(
+            keys[0] != INCLUDE_KEY,
+            "'{}' cannot be overridden, as files are included before overrides are applied.",
+            INCLUDE_KEY
+        );
+
+        let value = match configschema::find_value_kind::<Config>(&keys)? {
+            // Text does not need to be quoted, even if it looks like a
+            // number or date (e.g. `2026`).
+            ValueKind::Text => match parse_override_value(value) {
+                quoted_value @ toml::Value::String(_) => quoted_value,
+                _ => toml::Value::String(value.to_string()),
+            },
+            ValueKind::Other => parse_override_value(value),
+        };
+
+        Ok(Self { keys, value })
+    }
+
+    fn apply(&self, table: &mut toml::Table) -> Result<()> {
+        let (last_key, parent_keys) = self.keys.split_last().expect("keys are not empty");
+
+        let mut table = table;
+        for key in parent_keys {
+            table = table
+                .entry(key)
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+                .as_table_mut()
+                .ok_or_else(|| anyhow!("'{}' is not a table", key))?;
+        }
+
+        table.insert(last_key.clone(), self.value.clone());
+
+        Ok(())
+    }
+}
+
+/// Parse a value as TOML (e.g. `true`, `42`, `["a", "b"]`,
+/// `"quoted text"`), falling back to a string if it is not valid TOML
+/// (e.g. unquoted text like `/dev/input/event3`).
+fn parse_override_value(value: &str) -> toml::Value {
+    format!("value = {}", value)
+        .parse::<toml::Table>()
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(value.to_string()))
+}
+
+/// Collect overrides from environment variables like
+/// `VERBLEIBER_PARTY__PARTY_ID` (for `party.party_id`).
+fn get_env_overrides() -> Result<Vec<ConfigOverride>> {
+    let mut overrides = Vec::new();
+
+    for (name, value) in env::vars() {
+        if name == CLIENT_TOKEN_ENV_VAR {
+            continue;
+        }
+
+        let Some(path) = name.strip_prefix(OVERRIDE_ENV_VAR_PREFIX) else {
+            continue;
+        };
+
+        let path = path.to_lowercase();
+        let config_override = ConfigOverride::new(path.split(OVERRIDE_ENV_VAR_SEPARATOR), &value)
+            .with_context(|| format!("Invalid environment variable {}", name))?;
+        overrides.push(config_override);
+    }
+
+    Ok(overrides)
+}
+
+/// Collect overrides from environment variables, followed by those
+/// from command-line arguments (to take precedence).
+fn collect_overrides(cli_overrides: &[ConfigOverride]) -> Result<Vec<ConfigOverride>> {
+    let mut overrides = get_env_overrides()?;
+    overrides.extend_from_slice(cli_overrides);
+    Ok(overrides)
+}
+
+/// Load the configuration file (merged with the files it includes) as
+/// a table, with values overridden by environment variables and then by
+/// command-line arguments.
+fn load_config_table(path: &Path, overrides: &[ConfigOverride]) -> Result<toml::Table> {
+    let mut table = load_config_file_with_includes(path, &mut Vec::new())?;
+
+    for config_override in overrides {
+        config_override.apply(&mut table)?;
+    }
+
+    Ok(table)
+}
+
+/// Load a configuration file as a table, on top of the files it lists
+/// in `include` (relative to its own directory), in order.
+///
+/// `including_paths` is the chain of files that led to this one, to
+/// detect circular includes.
+fn load_config_file_with_includes(
+    path: &Path,
+    including_paths: &mut Vec<PathBuf>,
+) -> Result<toml::Table> {
+    let canonical_path =
+        canonicalize(path).with_context(|| format!("Could not read {}", path.display()))?;
+    ensure!(
+        !including_paths.contains(&canonical_path),
+        "Configuration file {} includes itself (via {}).",
+        path.display(),
+        including_paths
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect::<Vec<_>>()
+            .join(" -> ")
+    );
+
+    let text =
+        read_to_string(path).with_context(|| format!("Could not read {}", path.display()))?;
+    let mut table: toml::Table =
+        toml::from_str(&text).with_context(|| format!("Could not parse {}", path.display()))?;
+
+    let include_paths = match table.remove(INCLUDE_KEY) {
+        None => Vec::new(),
+        Some(value) => parse_include_paths(value)
+            .with_context(|| format!("Invalid '{}' in {}", INCLUDE_KEY, path.display()))?,
+    };
+
+    if include_paths.is_empty() {
+        return Ok(table);
+    }
+
+    let base_path = path.parent().unwrap_or(Path::new(""));
+
+    including_paths.push(canonical_path);
+
+    let mut merged_table = toml::Table::new();
+    for include_path in include_paths {
+        let included_table =
+            load_config_file_with_includes(&base_path.join(include_path), including_paths)?;
+        merge_tables(&mut merged_table, included_table);
+    }
+
+    including_paths.pop();
+
+    merge_tables(&mut merged_table, table);
+
+    Ok(merged_table)
+}
+
+/// Accept a single path or an array of paths.
+fn parse_include_paths(value: toml::Value) -> Result<Vec<PathBuf>> {
+    match value {
+        toml::Value::String(path) => Ok(vec![PathBuf::from(path)]),
+        toml::Value::Array(values) => values
+            .into_iter()
+            .map(|value| match value {
+                toml::Value::String(path) => Ok(PathBuf::from(path)),
+                _ => Err(anyhow!("Expected a path, got '{}'", value)),
+            })
+            .collect(),
+        _ => Err(anyhow!(
+            "Expected a path or an array of paths, got '{}'",
+            value
+        )),
+    }
+}
+
+/// Merge the overlay into the base table. Tables present in both are
+/// merged recursively, any other value in the overlay (including
+/// arrays) replaces the one in the base table.
+fn merge_tables(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, overlay_value) in overlay {
+        match (base.get_mut(&key), overlay_value) {
+            (Some(toml::Value::Table(base_table)), toml::Value::Table(overlay_table)) => {
+                merge_tables(base_table, overlay_table)
+            }
+            (_, overlay_value) => {
+                base.insert(key, overlay_value);
+            }
+        }
+    }
+}
+
+/// Return the names of the buttons defined in the configuration file,
+/// sorted, without requiring the rest of the configuration to be
+/// complete.
+pub(crate) fn load_button_names(
+    path: &Path,
+    cli_overrides: &[ConfigOverride],
+) -> Result<Vec<String>> {
+    let table = load_config_table(path, &collect_overrides(cli_overrides)?)?;
+
+    let get_keys = |table: Option<&toml::Value>| -> Vec<String> {
+        table
+            .and_then(|table| table.as_table())
+            .map(|table| table.keys().cloned().collect())
+            .unwrap_or_default()
+    };
+
+    let device_tables = table
+        .get("button_input_devices")
+        .and_then(|devices| devices.as_array())
+        .into_iter()
+        .flatten()
+        .chain(table.get("gpio"));
+
+    let mut button_names = get_keys(table.get("buttons_to_key_codes"));
+    for device_table in device_tables {
+        button_names.extend(get_keys(device_table.get("buttons_to_key_codes")));
+        button_names.extend(get_keys(device_table.get("buttons_to_lines")));
+    }
+
+    button_names.sort();
+    button_names.dedup();
+    Ok(button_names)
+}
+
+pub(crate) fn load_config(path: &Path, cli_overrides: &[ConfigOverride]) -> Result<Config> {
+    let table = load_config_table(path, &collect_overrides(cli_overrides)?)?;
+    parse_config(table)
+}
+
+fn parse_config(table: toml::Table) -> Result<Config> {
+    let config: Config = table.try_into()?;
+
+    config.validate_button_references()?;
+    config.validate_gpio_lines()?;
+    config.validate_axis_thresholds()?;
+    config.validate_greeting_style()?;
+    config.warn_about_missing_default_admin_buttons();
+
     Ok(config)
 }
+
+/// Print the effective configuration, with secrets masked.
+pub(crate) fn show_config(path: &Path, cli_overrides: &[ConfigOverride]) -> Result<()> {
+    let mut table = load_config_table(path, &collect_overrides(cli_overrides)?)?;
+
+    // Fail on invalid configuration just like other commands would.
+    parse_config(table.clone())?;
+
+    mask_secrets(&mut table);
+    print!("{}", toml::to_string(&table)?);
+
+    Ok(())
+}
+
+fn mask_secrets(table: &mut toml::Table) {
+    for (key, value) in table.iter_mut() {
+        if SECRET_KEYS.contains(&key.as_str()) {
+            *value = toml::Value::String(MASKED_VALUE.to_string());
+        } else {
+            mask_secrets_in_value(value);
+        }
+    }
+}
+
+fn mask_secrets_in_value(value: &mut toml::Value) {
+    match value {
+        toml::Value::Table(table) => mask_secrets(table),
+        toml::Value::Array(values) => values.iter_mut().for_each(mask_secrets_in_value),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testutil::TempDir;
+
+    use super::*;
+
+    const MINIMAL_CONFIG: &str = r#"
+        sounds_path = "sounds"
+
+        [api]
+        base_url = "http://localhost:8080/api"
+        tls_verify = true
+        timeout_in_seconds = 5
+
+        [party]
+        party_id = "lanparty"
+        buttons_to_whereabouts = {}
+        whereabouts_sounds = {}
+    "#;
+
+    fn load_with_overrides(overrides: &[&str]) -> Result<(toml::Table, Config)> {
+        let overrides: Vec<ConfigOverride> = overrides
+            .iter()
+            .map(|s| ConfigOverride::from_str(s))
+            .collect::<Result<_>>()?;
+
+        let mut table: toml::Table = toml::from_str(MINIMAL_CONFIG)?;
+        for config_override in &overrides {
+            config_override.apply(&mut table)?;
+        }
+
+        let config = parse_config(table.clone())?;
+        Ok((table, config))
+    }
+
+    #[test]
+    fn override_value_is_parsed_as_toml_or_text() {
+        assert_eq!(parse_override_value("42"), toml::Value::Integer(42));
+        assert_eq!(parse_override_value("true"), toml::Value::Boolean(true));
+        assert_eq!(
+            parse_override_value(r#""quoted text""#),
+            toml::Value::String("quoted text".to_string())
+        );
+        assert_eq!(
+            parse_override_value("/dev/input/event3"),
+            toml::Value::String("/dev/input/event3".to_string())
+        );
+    }
+
+    #[test]
+    fn override_creates_missing_tables() {
+        let mut table = toml::Table::new();
+        ConfigOverride::from_str("status_server.address=0.0.0.0:9473")
+            .unwrap()
+            .apply(&mut table)
+            .unwrap();
+
+        assert_eq!(
+            table["status_server"]["address"].as_str(),
+            Some("0.0.0.0:9473")
+        );
+    }
+
+    #[test]
+    fn override_of_unknown_property_is_rejected() {
+        for s in [
+            "party.partyid=lan",
+            "party_party_id=lan",
+            "home=/x",
+            "sounds_path.extra=1",
+            "admin.buttons_to_actions.button1:sideways=shut_down",
+        ] {
+            assert!(ConfigOverride::from_str(s).is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn gpio_line_mapped_to_multiple_buttons_is_rejected() {
+        let error = load_with_overrides(&[
+            "gpio.chip=/dev/gpiochip0",
+            "gpio.buttons_to_lines={ red = 17, green = 27, blue = 17 }",
+        ])
+        .err()
+        .expect("duplicate line is rejected");
+
+        assert_eq!(
+            error.to_string(),
+            "GPIO line 17 is mapped to both buttons blue and red in `gpio.buttons_to_lines`."
+        );
+    }
+
+    #[test]
+    fn axis_thresholds_without_range_in_between_are_rejected() {
+        let error = load_with_overrides(&[
+            "buttons.axes=[{ axis = \"hat0x\", negative_threshold = 1, positive_threshold = 1 }]",
+        ])
+        .err()
+        .expect("thresholds are rejected");
+
+        assert_eq!(
+            error.to_string(),
+            "Negative threshold (1) of axis 'hat0x' must be lower than its positive threshold (1)."
+        );
+    }
+
+    #[test]
+    fn speech_greeting_without_tts_is_rejected() {
+        let error = load_with_overrides(&["party.greeting_style=speech"])
+            .err()
+            .expect("greeting style is rejected");
+
+        assert_eq!(
+            error.to_string(),
+            "Greeting style 'speech' requires section `tts` to be configured."
+        );
+
+        assert!(
+            load_with_overrides(&[
+                "party.greeting_style=speech",
+                "tts.command=[\"espeak-ng\", \"-w\", \"{output}\", \"{text}\"]",
+                "tts.cache_path=tts-cache",
+            ])
+            .is_ok()
+        );
+    }
+
+    #[test]
+    fn override_of_includes_is_rejected() {
+        let error = ConfigOverride::from_str("include=other.toml").unwrap_err();
+
+        assert!(error.to_string().contains("cannot be overridden"));
+    }
+
+    #[test]
+    fn override_of_property_with_arbitrary_name_is_accepted() {
+        let (table, config) = load_with_overrides(&[
+            "sound_variants.sign_on_succeeded=[\"moin\", \"ahoi\"]",
+            "button_input_device=/dev/input/event3",
+            "buttons_to_key_codes.button1=KEY_1",
+            "admin.buttons_to_actions.button1:long=shut_down",
+            "party.greeting_style=name_sounds",
+        ])
+        .unwrap();
+
+        assert_eq!(config.sound_variants["sign_on_succeeded"].len(), 2);
+        assert_eq!(
+            table["admin"]["buttons_to_actions"]["button1:long"].as_str(),
+            Some("shut_down")
+        );
+    }
+
+    #[test]
+    fn numeric_override_of_text_property_is_applied_as_text() {
+        let (table, config) = load_with_overrides(&[
+            "party.party_id=2026",
+            "api.timeout_in_seconds=30",
+            "api.base_url=1.50",
+        ])
+        .unwrap();
+
+        assert_eq!(config.party.party_id, "2026");
+        assert_eq!(config.api.base_url, "1.50");
+        assert_eq!(table["api"]["timeout_in_seconds"].as_integer(), Some(30));
+    }
+
+    #[test]
+    fn quoted_override_of_text_property_is_unquoted() {
+        let (_, config) = load_with_overrides(&["party.party_id=\"2026\""]).unwrap();
+
+        assert_eq!(config.party.party_id, "2026");
+    }
+
+    #[test]
+    fn date_like_override_of_text_property_is_accepted() {
+        let (_, config) = load_with_overrides(&["party.party_id=2026-08-01"]).unwrap();
+
+        assert_eq!(config.party.party_id, "2026-08-01");
+    }
+
+    #[test]
+    fn text_override_of_numeric_property_is_rejected() {
+        assert!(load_with_overrides(&["api.timeout_in_seconds=soon"]).is_err());
+    }
+
+    #[test]
+    fn merging_tables_merges_nested_tables_and_replaces_other_values() {
+        let mut base: toml::Table = toml::from_str(
+            r#"
+            sounds_path = "sounds"
+
+            [admin]
+            tags = ["1", "2"]
+
+            [party]
+            party_id = "lanparty"
+            greeting_style = "tag_sound"
+            "#,
+        )
+        .unwrap();
+        let overlay: toml::Table = toml::from_str(
+            r#"
+            [admin]
+            tags = ["3"]
+
+            [party]
+            party_id = "lanparty-2026"
+            "#,
+        )
+        .unwrap();
+
+        merge_tables(&mut base, overlay);
+
+        assert_eq!(base["sounds_path"].as_str(), Some("sounds"));
+        assert_eq!(
+            base["admin"]["tags"],
+            toml::Value::Array(vec![toml::Value::String("3".to_string())])
+        );
+        assert_eq!(base["party"]["party_id"].as_str(), Some("lanparty-2026"));
+        assert_eq!(base["party"]["greeting_style"].as_str(), Some("tag_sound"));
+    }
+
+    #[test]
+    fn including_file_takes_precedence_over_included_files_in_order() {
+        let dir = TempDir::new("includes").with_files(&[
+            (
+                "main.toml",
+                r#"
+                include = ["base.toml", "site.toml"]
+                sounds_path = "main-sounds"
+                "#,
+            ),
+            (
+                "base.toml",
+                r#"
+                sounds_path = "base-sounds"
+                sound_pack = "base"
+                [party]
+                party_id = "base-party"
+                "#,
+            ),
+            (
+                "site.toml",
+                r#"
+                sound_pack = "site"
+                "#,
+            ),
+        ]);
+
+        let table =
+            load_config_file_with_includes(&dir.path().join("main.toml"), &mut Vec::new()).unwrap();
+
+        assert_eq!(table["sounds_path"].as_str(), Some("main-sounds"));
+        assert_eq!(table["sound_pack"].as_str(), Some("site"));
+        assert_eq!(table["party"]["party_id"].as_str(), Some("base-party"));
+        assert!(!table.contains_key(INCLUDE_KEY));
+    }
+
+    #[test]
+    fn circular_include_is_rejected() {
+        let dir = TempDir::new("circular-includes").with_files(&[
+            ("a.toml", r#"include = "b.toml""#),
+            ("b.toml", r#"include = "a.toml""#),
+        ]);
+
+        let result = load_config_file_with_includes(&dir.path().join("a.toml"), &mut Vec::new());
+
+        let error = result.expect_err("circular include is rejected");
+        assert!(error.to_string().contains("includes itself"));
+    }
+}
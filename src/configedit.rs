@@ -0,0 +1,124 @@
+/*
+ * Copyright 2022-2026 Jochen Kupperschmidt
+ * License: MIT
+ */
+
+//! Editing of configuration files while preserving their comments and
+//! layout
+
+use std::fs::{File, OpenOptions, canonicalize, metadata, read_to_string, remove_file, rename};
+use std::io::Write;
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt, fchown};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use anyhow::{Context, Result};
+use toml_edit::{DocumentMut, TableLike};
+
+const TEMPLATE: &str = include_str!("../config_example.toml");
+
+/// Load the configuration file as an editable document, or start with
+/// an empty one if it does not exist yet.
+pub(crate) fn load_document(path: &Path) -> Result<DocumentMut> {
+    if !path.exists() {
+        return Ok(DocumentMut::new());
+    }
+
+    parse_document(path)
+}
+
+/// Load the configuration file as an editable document, or start with
+/// the example configuration if it does not exist yet.
+pub(crate) fn load_document_or_template(path: &Path) -> Result<DocumentMut> {
+    if !path.exists() {
+        log::info!("Creating {} from template.", path.display());
+        return Ok(TEMPLATE.parse()?);
+    }
+
+    parse_document(path)
+}
+
+fn parse_document(path: &Path) -> Result<DocumentMut> {
+    let text =
+        read_to_string(path).with_context(|| format!("Could not read {}", path.display()))?;
+    text.parse()
+        .with_context(|| format!("Could not parse {}", path.display()))
+}
+
+/// Write the document to the configuration file.
+///
+/// The document is written to a temporary file next to the
+/// configuration file first, which then replaces it. This way, an
+/// interrupted write does not leave a truncated configuration file
+/// behind.
+///
+/// As the document might contain secrets, the file ends up readable and
+/// writable only by its owner (which is kept if the file exists).
+pub(crate) fn write_document(path: &Path, document: &DocumentMut) -> Result<()> {
+    // Replace the actual file, not a symlink to it.
+    let path = &canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+
+    let temp_path = get_temp_path(path)?;
+
+    let result = write_temp_file(&temp_path, path, document).and_then(|()| {
+        rename(&temp_path, path).with_context(|| format!("Could not replace {}", path.display()))
+    });
+
+    if result.is_err() {
+        let _ = remove_file(&temp_path);
+    }
+
+    result
+}
+
+fn get_temp_path(path: &Path) -> Result<PathBuf> {
+    let file_name = path
+        .file_name()
+        .with_context(|| format!("{} is not a file name", path.display()))?;
+    let temp_file_name = format!(".{}.{}.tmp", file_name.to_string_lossy(), process::id());
+    Ok(path.with_file_name(temp_file_name))
+}
+
+fn write_temp_file(temp_path: &Path, path: &Path, document: &DocumentMut) -> Result<()> {
+    // Created with restricted permissions right away so that the
+    // contents are never accessible by others.
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(temp_path)
+        .with_context(|| format!("Could not create {}", temp_path.display()))?;
+
+    if let Ok(existing) = metadata(path) {
+        keep_ownership(&file, path, existing.uid(), existing.gid());
+    }
+
+    file.write_all(document.to_string().as_bytes())
+        .and_then(|()| file.sync_all())
+        .with_context(|| format!("Could not write {}", temp_path.display()))
+}
+
+/// Give the replacement the owner and group of the existing file (e.g.
+/// if run as root on a file owned by the service user).
+fn keep_ownership(file: &File, path: &Path, uid: u32, gid: u32) {
+    if let Err(e) = fchown(file, Some(uid), Some(gid)) {
+        log::warn!(
+            "Could not keep owner and group of {} ({}:{}): {e}",
+            path.display(),
+            uid,
+            gid
+        );
+    }
+}
+
+/// Return the table for the key, creating it if necessary.
+pub(crate) fn get_or_insert_table<'a>(
+    table: &'a mut dyn TableLike,
+    key: &str,
+) -> Result<&'a mut dyn TableLike> {
+    table
+        .entry(key)
+        .or_insert(toml_edit::table())
+        .as_table_like_mut()
+        .with_context(|| format!("'{}' is not a table", key))
+}
@@ -0,0 +1,239 @@
+/*
+ * Copyright 2022-2026 Jochen Kupperschmidt
+ * License: MIT
+ */
+
+//! Inspection of the configuration's structure, as derived from the
+//! types it is deserialized into, to check overrides before applying
+//! them
+
+use std::fmt;
+
+use anyhow::{Result, anyhow};
+use serde::de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, MapAccess, Visitor};
+
+/// Kind of value a configuration property expects
+#[derive(Debug, PartialEq)]
+pub(crate) enum ValueKind {
+    Text,
+    Other,
+}
+
+/// Determine the kind of value the (possibly nested) property expects,
+/// failing if `T` has no such property.
+///
+/// Properties below values whose structure cannot be determined up
+/// front (e.g. light configurations, which depend on their `type`) are
+/// not checked.
+pub(crate) fn find_value_kind<T: DeserializeOwned>(keys: &[String]) -> Result<ValueKind> {
+    let probe = KeyProbe { keys, position: 0 };
+
+    match T::deserialize(probe) {
+        Err(ProbeOutcome::Found(kind)) => Ok(kind),
+        Err(ProbeOutcome::NotFound(position)) => Err(anyhow!(
+            "Unknown configuration key '{}'",
+            keys[..=position].join(".")
+        )),
+        Err(ProbeOutcome::Invalid(message)) => Err(anyhow!(
+            "Invalid configuration key '{}': {}",
+            keys.join("."),
+            message
+        )),
+        Ok(_) => Ok(ValueKind::Other),
+    }
+}
+
+/// Result of probing, returned as error to stop deserialization
+#[derive(Debug)]
+enum ProbeOutcome {
+    Found(ValueKind),
+    /// Position of the first key that does not exist
+    NotFound(usize),
+    /// A key that exists only as a value of another type (e.g. not a
+    /// valid button name)
+    Invalid(String),
+}
+
+impl fmt::Display for ProbeOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl std::error::Error for ProbeOutcome {}
+
+impl de::Error for ProbeOutcome {
+    fn custom<T: fmt::Display>(message: T) -> Self {
+        Self::Invalid(message.to_string())
+    }
+}
+
+/// Deserializer that, instead of providing values, follows the keys
+/// down into the structure being deserialized.
+#[derive(Clone, Copy)]
+struct KeyProbe<'a> {
+    keys: &'a [String],
+    position: usize,
+}
+
+impl<'a> KeyProbe<'a> {
+    /// Reached a value that cannot contain further keys.
+    fn leaf<T>(self, kind: ValueKind) -> Result<T, ProbeOutcome> {
+        if self.position == self.keys.len() {
+            Err(ProbeOutcome::Found(kind))
+        } else {
+            Err(ProbeOutcome::NotFound(self.position))
+        }
+    }
+
+    /// Reached a table, either with fixed (`fields`) or arbitrary keys.
+    fn table<'de, V: Visitor<'de>>(
+        self,
+        fields: Option<&[&str]>,
+        visitor: V,
+    ) -> Result<V::Value, ProbeOutcome> {
+        let Some(key) = self.keys.get(self.position) else {
+            return Err(ProbeOutcome::Found(ValueKind::Other));
+        };
+
+        if fields.is_some_and(|fields| !fields.contains(&key.as_str())) {
+            return Err(ProbeOutcome::NotFound(self.position));
+        }
+
+        visitor.visit_map(SingleEntry {
+            key: Some(key),
+            value: KeyProbe {
+                keys: self.keys,
+                position: self.position + 1,
+            },
+        })
+    }
+}
+
+macro_rules! deserialize_leaf {
+    ($($method:ident => $kind:expr,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+                self.leaf($kind)
+            }
+        )*
+    };
+}
+
+impl<'de, 'a> de::Deserializer<'de> for KeyProbe<'a> {
+    type Error = ProbeOutcome;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(ProbeOutcome::Found(ValueKind::Other))
+    }
+
+    deserialize_leaf! {
+        deserialize_bool => ValueKind::Other,
+        deserialize_i8 => ValueKind::Other,
+        deserialize_i16 => ValueKind::Other,
+        deserialize_i32 => ValueKind::Other,
+        deserialize_i64 => ValueKind::Other,
+        deserialize_u8 => ValueKind::Other,
+        deserialize_u16 => ValueKind::Other,
+        deserialize_u32 => ValueKind::Other,
+        deserialize_u64 => ValueKind::Other,
+        deserialize_f32 => ValueKind::Other,
+        deserialize_f64 => ValueKind::Other,
+        deserialize_char => ValueKind::Text,
+        deserialize_str => ValueKind::Text,
+        deserialize_string => ValueKind::Text,
+        deserialize_bytes => ValueKind::Other,
+        deserialize_byte_buf => ValueKind::Other,
+        deserialize_unit => ValueKind::Other,
+        deserialize_seq => ValueKind::Other,
+        deserialize_identifier => ValueKind::Text,
+        deserialize_ignored_any => ValueKind::Other,
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.leaf(ValueKind::Other)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.leaf(ValueKind::Other)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.leaf(ValueKind::Other)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.table(None, visitor)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.table(Some(fields), visitor)
+    }
+
+    /// Enums are given as text (e.g. `greeting_style = "speech"`).
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.leaf(ValueKind::Text)
+    }
+}
+
+/// A table with only the key to follow
+struct SingleEntry<'a> {
+    key: Option<&'a str>,
+    value: KeyProbe<'a>,
+}
+
+impl<'de, 'a> MapAccess<'de> for SingleEntry<'a> {
+    type Error = ProbeOutcome;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        match self.key.take() {
+            Some(key) => seed.deserialize(key.into_deserializer()).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Self::Error> {
+        seed.deserialize(self.value)
+    }
+}
@@ -0,0 +1,283 @@
+/*
+ * Copyright 2022-2026 Jochen Kupperschmidt
+ * License: MIT
+ */
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use flume::RecvTimeoutError;
+
+use crate::buttons::{Button, ButtonDiagnostics};
+use crate::config::ButtonsConfig;
+use crate::gestures::{ButtonEdge, ButtonEdgeReceiver, ButtonEdgeSender};
+
+/// Filters out contact bounce of mechanical buttons.
+///
+/// A change of a button's state is accepted right away if the previous
+/// accepted change is at least the debounce interval ago. Otherwise, it
+/// is held back until the interval has passed: if the button returns to
+/// its accepted state until then, both changes are dropped as a bounce,
+/// else the change is accepted late (e.g. the release of a tap shorter
+/// than the interval). This way, the accepted state of a button always
+/// ends up matching its actual state.
+///
+/// Repeated presses or releases without a change in between are
+/// ignored.
+pub(crate) struct Debouncer {
+    default_interval: Duration,
+    intervals: HashMap<Button, Duration>,
+    states: HashMap<Button, DebounceState>,
+}
+
+struct DebounceState {
+    pressed: bool,
+    /// When the last accepted change happened
+    changed_at: Instant,
+    /// Latest change, held back as it happened within the debounce
+    /// interval
+    pending_edge: Option<ButtonEdge>,
+}
+
+pub(crate) enum Debounced {
+    /// To be passed on now
+    Accepted(ButtonEdge),
+    /// Held back until the debounce interval has passed (see
+    /// `Debouncer::take_settled_edges`)
+    Deferred,
+    /// To be dropped as a bounce or repetition
+    Ignored,
+}
+
+impl Debouncer {
+    pub(crate) fn new(config: &ButtonsConfig) -> Self {
+        Self {
+            default_interval: Duration::from_millis(config.debounce_in_ms),
+            intervals: config
+                .debounce_in_ms_by_button
+                .iter()
+                .map(|(button, ms)| (button.clone(), Duration::from_millis(*ms)))
+                .collect(),
+            states: HashMap::new(),
+        }
+    }
+
+    /// Pass button presses and releases on to gesture detection, unless
+    /// they are bounces.
+    pub(crate) fn run(
+        mut self,
+        edge_receiver: ButtonEdgeReceiver,
+        edge_sender: ButtonEdgeSender,
+        diagnostics: Arc<ButtonDiagnostics>,
+    ) -> Result<()> {
+        let forward = |edge: ButtonEdge| -> Result<()> {
+            if edge.pressed {
+                diagnostics.count(&edge.button, |counters| counters.presses += 1);
+            }
+            edge_sender.send(edge)?;
+            Ok(())
+        };
+
+        loop {
+            let received = match self.find_next_deadline() {
+                Some(deadline) => edge_receiver.recv_deadline(deadline),
+                None => edge_receiver
+                    .recv()
+                    .map_err(|_| RecvTimeoutError::Disconnected),
+            };
+
+            match received {
+                Ok(edge) => {
+                    // Changes that have settled in the meantime come
+                    // first.
+                    for settled_edge in self.take_settled_edges(edge.time) {
+                        forward(settled_edge)?;
+                    }
+
+                    let button = edge.button.clone();
+                    match self.filter(edge) {
+                        Debounced::Accepted(edge) => forward(edge)?,
+                        Debounced::Deferred => {}
+                        Debounced::Ignored => {
+                            diagnostics.count(&button, |counters| counters.bounces_ignored += 1)
+                        }
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    for settled_edge in self.take_settled_edges(Instant::now()) {
+                        forward(settled_edge)?;
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            }
+        }
+    }
+
+    fn filter(&mut self, edge: ButtonEdge) -> Debounced {
+        let interval = self.get_interval(&edge.button);
+
+        let Some(state) = self.states.get_mut(&edge.button) else {
+            self.states.insert(
+                edge.button.clone(),
+                DebounceState {
+                    pressed: edge.pressed,
+                    changed_at: edge.time,
+                    pending_edge: None,
+                },
+            );
+            return Debounced::Accepted(edge);
+        };
+
+        if edge.pressed == state.pressed {
+            // A repetition, or the end of a bounce
+            state.pending_edge = None;
+            return Debounced::Ignored;
+        }
+
+        if edge.time.duration_since(state.changed_at) >= interval {
+            state.pressed = edge.pressed;
+            state.changed_at = edge.time;
+            state.pending_edge = None;
+            Debounced::Accepted(edge)
+        } else {
+            state.pending_edge = Some(edge);
+            Debounced::Deferred
+        }
+    }
+
+    /// Accept and return held back changes whose debounce interval has
+    /// passed.
+    fn take_settled_edges(&mut self, now: Instant) -> Vec<ButtonEdge> {
+        let mut settled_edges = Vec::new();
+
+        for (button, state) in self.states.iter_mut() {
+            let interval = self
+                .intervals
+                .get(button)
+                .copied()
+                .unwrap_or(self.default_interval);
+            let settled_at = state.changed_at + interval;
+            if state.pending_edge.is_none() || now < settled_at {
+                continue;
+            }
+
+            if let Some(edge) = state.pending_edge.take() {
+                state.pressed = edge.pressed;
+                state.changed_at = settled_at;
+                settled_edges.push(edge);
+            }
+        }
+
+        settled_edges.sort_by_key(|edge| edge.time);
+        settled_edges
+    }
+
+    fn find_next_deadline(&self) -> Option<Instant> {
+        self.states
+            .iter()
+            .filter(|(_, state)| state.pending_edge.is_some())
+            .map(|(button, state)| state.changed_at + self.get_interval(button))
+            .min()
+    }
+
+    fn get_interval(&self, button: &Button) -> Duration {
+        self.intervals
+            .get(button)
+            .copied()
+            .unwrap_or(self.default_interval)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    const INTERVAL: Duration = Duration::from_millis(30);
+
+    fn create_debouncer() -> Debouncer {
+        Debouncer::new(&ButtonsConfig {
+            debounce_in_ms: INTERVAL.as_millis() as u64,
+            ..Default::default()
+        })
+    }
+
+    fn edge(pressed: bool, time: Instant) -> ButtonEdge {
+        ButtonEdge {
+            button: Button::from_str("button1").unwrap(),
+            pressed,
+            time,
+            source: "test".to_string(),
+        }
+    }
+
+    fn is_accepted(debounced: Debounced, pressed: bool) -> bool {
+        matches!(debounced, Debounced::Accepted(edge) if edge.pressed == pressed)
+    }
+
+    #[test]
+    fn bounce_is_ignored() {
+        let mut debouncer = create_debouncer();
+        let t0 = Instant::now();
+        let ms = Duration::from_millis;
+
+        assert!(is_accepted(debouncer.filter(edge(true, t0)), true));
+        assert!(matches!(
+            debouncer.filter(edge(false, t0 + ms(2))),
+            Debounced::Deferred
+        ));
+        assert!(matches!(
+            debouncer.filter(edge(true, t0 + ms(4))),
+            Debounced::Ignored
+        ));
+
+        // The button is still held after the interval.
+        assert!(debouncer.take_settled_edges(t0 + ms(50)).is_empty());
+        assert_eq!(debouncer.find_next_deadline(), None);
+
+        assert!(is_accepted(
+            debouncer.filter(edge(false, t0 + ms(200))),
+            false
+        ));
+    }
+
+    #[test]
+    fn repetition_is_ignored() {
+        let mut debouncer = create_debouncer();
+        let t0 = Instant::now();
+
+        assert!(is_accepted(debouncer.filter(edge(true, t0)), true));
+        assert!(matches!(
+            debouncer.filter(edge(true, t0 + Duration::from_millis(100))),
+            Debounced::Ignored
+        ));
+    }
+
+    #[test]
+    fn release_of_tap_shorter_than_interval_is_accepted_late() {
+        let mut debouncer = create_debouncer();
+        let t0 = Instant::now();
+        let ms = Duration::from_millis;
+
+        assert!(is_accepted(debouncer.filter(edge(true, t0)), true));
+        assert!(matches!(
+            debouncer.filter(edge(false, t0 + ms(10))),
+            Debounced::Deferred
+        ));
+        assert_eq!(debouncer.find_next_deadline(), Some(t0 + INTERVAL));
+
+        assert!(debouncer.take_settled_edges(t0 + ms(20)).is_empty());
+        let settled_edges = debouncer.take_settled_edges(t0 + INTERVAL);
+        assert_eq!(settled_edges.len(), 1);
+        assert!(!settled_edges[0].pressed);
+
+        // The next press is not mistaken for a repetition.
+        assert!(is_accepted(
+            debouncer.filter(edge(true, t0 + ms(100))),
+            true
+        ));
+    }
+}
@@ -3,32 +3,447 @@
  * License: MIT
  */
 
-use anyhow::{Result, anyhow};
-use evdev::Device;
+use std::fs::{self, File};
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
+use anyhow::{Context, Error, Result, anyhow, bail};
+use evdev::{AbsoluteAxisCode, Device, KeyCode, LedCode};
+
+use crate::axes;
+use crate::keycodenames::{self, KeyCodeNameMapping};
+
+/// Either the path of a device (e.g. `/dev/input/event23`) or criteria
+/// to find it by (e.g. `usb=046d:c216,has=btn_trigger`)
 pub(crate) type DeviceName = String;
 
-pub(crate) fn open_input_device(device_name: DeviceName, label: String) -> Result<Device> {
-    Device::open(device_name)
-        .map_err(|e| anyhow!("Could not open {}: {}", label, e))
-        .and_then(|mut device| {
-            log::info!(
-                "Opened {} \"{}\".",
-                label,
-                device.name().unwrap_or("unnamed device")
-            );
+pub(crate) fn open_input_device(
+    device_name: DeviceName,
+    label: String,
+    grab: bool,
+) -> Result<Device> {
+    let (path, mut device) =
+        find_device(&device_name).map_err(|e| anyhow!("Could not open {}: {}", label, e))?;
 
-            grab_input_device(&mut device, label)?;
+    log::info!(
+        "Opened {} \"{}\".",
+        label,
+        device.name().unwrap_or("unnamed device")
+    );
 
-            Ok(device)
-        })
+    if grab {
+        grab_input_device(&mut device, &path, label)?;
+    } else {
+        log::info!("Not requesting exclusive access to {}.", label);
+    }
+
+    Ok(device)
+}
+
+/// Open the device at the path or, if criteria are given instead, the
+/// single device that matches them.
+pub(crate) fn open_device(device_name: &str) -> Result<Device> {
+    find_device(device_name).map(|(_, device)| device)
 }
 
-fn grab_input_device(device: &mut Device, label: String) -> Result<()> {
+fn find_device(device_name: &str) -> Result<(PathBuf, Device)> {
+    if !device_name.contains('=') {
+        let path = PathBuf::from(device_name);
+        let device = Device::open(&path).map_err(|e| describe_open_error(&path, e))?;
+        return Ok((path, device));
+    }
+
+    let matcher: DeviceMatcher = device_name.parse()?;
+
+    let mut candidates: Vec<(PathBuf, Device)> = evdev::enumerate()
+        .filter(|(_, device)| matcher.matches(device))
+        .collect();
+
+    match candidates.len() {
+        0 => {
+            let mut message = format!("No device matches '{}'", device_name);
+            if let Some(path) = find_inaccessible_device() {
+                message.push_str(&format!(
+                    "; some devices are not accessible.\n{}",
+                    describe_permission_problem(&path)
+                ));
+            }
+            bail!(message)
+        }
+        1 => {
+            let (path, device) = candidates.remove(0);
+            log::debug!("Device '{}' resolved to {}.", device_name, path.display());
+            Ok((path, device))
+        }
+        _ => {
+            let mut paths: Vec<String> = candidates
+                .iter()
+                .map(|(path, _)| path.display().to_string())
+                .collect();
+            paths.sort();
+            bail!(
+                "Multiple devices match '{}': {}",
+                device_name,
+                paths.join(", ")
+            )
+        }
+    }
+}
+
+fn grab_input_device(device: &mut Device, path: &Path, label: String) -> Result<()> {
     device
         .grab()
-        .map_err(|e| anyhow!("Could not get exclusive access to {}: {}", label, e))
+        .map_err(|e| {
+            let mut message = format!("Could not get exclusive access to {}: {}", label, e);
+            if e.raw_os_error() == Some(libc::EBUSY) {
+                message.push('\n');
+                message.push_str(&describe_busy_device(path));
+            }
+            anyhow!(message)
+        })
         .map(|()| {
             log::info!("Successfully obtained exclusive access to {}.", label);
         })
 }
+
+fn describe_open_error(path: &Path, error: io::Error) -> Error {
+    if error.kind() == io::ErrorKind::PermissionDenied {
+        anyhow!("{}\n{}", error, describe_permission_problem(path))
+    } else {
+        anyhow!(error)
+    }
+}
+
+/// Explain why access to the device node is denied, and how to fix it.
+fn describe_permission_problem(path: &Path) -> String {
+    let Ok(metadata) = fs::metadata(path) else {
+        return format!(
+            "Hint: Check the permissions of {} and its group.",
+            path.display()
+        );
+    };
+
+    let gid = metadata.gid();
+    let group_name = find_group_name(gid).unwrap_or_else(|| gid.to_string());
+
+    if is_member_of_group(gid) {
+        format!(
+            "The current user is a member of group '{}', which owns {} (mode {:o}), but access is still denied.\n\
+             Hint: Check the permissions of the device node (e.g. udev rules).",
+            group_name,
+            path.display(),
+            metadata.mode() & 0o777
+        )
+    } else {
+        format!(
+            "{} belongs to group '{}', of which the current user is not a member.\n\
+             Hint: Add the user to that group (e.g. `sudo usermod -aG {} $USER`) and log in again.",
+            path.display(),
+            group_name,
+            group_name
+        )
+    }
+}
+
+/// Explain which processes might hold the device, and how to proceed.
+fn describe_busy_device(path: &Path) -> String {
+    let processes = find_processes_using(path);
+
+    let mut message = if processes.is_empty() {
+        "Another process holds the device, but it could not be determined which one (try as root).\n"
+            .to_string()
+    } else {
+        let names: Vec<String> = processes
+            .iter()
+            .map(|(pid, name)| format!("{} (PID {})", name, pid))
+            .collect();
+        format!(
+            "Processes that have {} open: {}\n",
+            path.display(),
+            names.join(", ")
+        )
+    };
+
+    message.push_str(
+        "Hint: Stop the process holding the device, or disable exclusive access for this device.",
+    );
+    message
+}
+
+/// Return ID and name of other processes that have the file open.
+///
+/// Processes of other users are only visible when run as root.
+fn find_processes_using(path: &Path) -> Vec<(u32, String)> {
+    let Ok(target) = fs::canonicalize(path) else {
+        return Vec::new();
+    };
+    let own_pid = std::process::id();
+
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+
+    let mut processes: Vec<(u32, String)> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.file_name().to_str()?.parse::<u32>().ok())
+        .filter(|pid| *pid != own_pid)
+        .filter(|pid| {
+            fs::read_dir(format!("/proc/{}/fd", pid)).is_ok_and(|fds| {
+                fds.filter_map(|fd| fd.ok())
+                    .any(|fd| fs::read_link(fd.path()).is_ok_and(|link| link == target))
+            })
+        })
+        .map(|pid| {
+            let name = fs::read_to_string(format!("/proc/{}/comm", pid))
+                .map(|name| name.trim().to_string())
+                .unwrap_or_else(|_| "<unknown>".to_string());
+            (pid, name)
+        })
+        .collect();
+
+    processes.sort();
+    processes
+}
+
+/// Return the first event device that cannot be opened for lack of
+/// permissions.
+fn find_inaccessible_device() -> Option<PathBuf> {
+    let mut paths: Vec<PathBuf> = fs::read_dir("/dev/input")
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("event"))
+        })
+        .collect();
+    paths.sort();
+
+    paths
+        .into_iter()
+        .find(|path| File::open(path).is_err_and(|e| e.kind() == io::ErrorKind::PermissionDenied))
+}
+
+fn find_group_name(gid: u32) -> Option<String> {
+    fs::read_to_string("/etc/group")
+        .ok()?
+        .lines()
+        .find_map(|line| {
+            let mut fields = line.split(':');
+            let name = fields.next()?;
+            let group_id = fields.nth(1)?.parse::<u32>().ok()?;
+            (group_id == gid).then(|| name.to_string())
+        })
+}
+
+fn is_member_of_group(gid: u32) -> bool {
+    // SAFETY: Both calls only write into the buffer, which is sized as
+    // reported by the first call.
+    unsafe {
+        if libc::getegid() == gid {
+            return true;
+        }
+
+        let count = libc::getgroups(0, std::ptr::null_mut());
+        if count <= 0 {
+            return false;
+        }
+
+        let mut groups = vec![0 as libc::gid_t; count as usize];
+        let count = libc::getgroups(count, groups.as_mut_ptr());
+        count > 0 && groups[..count as usize].contains(&gid)
+    }
+}
+
+/// Criteria a device has to meet, separated by commas:
+///
+/// - `usb=<vendor>:<product>`: USB vendor and product ID (hexadecimal)
+/// - `name=<text>`: part of the device's name
+/// - `phys=<text>`: part of the device's physical path
+/// - `has=<code>`: a supported key, axis, or LED (e.g. `btn_trigger`,
+///   `abs_hat0x`, `led_numl`)
+#[derive(Default)]
+struct DeviceMatcher {
+    vendor_product: Option<(u16, u16)>,
+    name: Option<String>,
+    phys: Option<String>,
+    capabilities: Vec<Capability>,
+}
+
+impl DeviceMatcher {
+    fn matches(&self, device: &Device) -> bool {
+        if let Some((vendor, product)) = self.vendor_product {
+            let input_id = device.input_id();
+            if input_id.vendor() != vendor || input_id.product() != product {
+                return false;
+            }
+        }
+
+        if let Some(name) = &self.name
+            && !device.name().is_some_and(|value| value.contains(name))
+        {
+            return false;
+        }
+
+        if let Some(phys) = &self.phys
+            && !device
+                .physical_path()
+                .is_some_and(|value| value.contains(phys))
+        {
+            return false;
+        }
+
+        self.capabilities
+            .iter()
+            .all(|capability| capability.is_supported_by(device))
+    }
+}
+
+impl FromStr for DeviceMatcher {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut matcher = Self::default();
+
+        for criterion in s.split(',') {
+            let (key, value) = criterion
+                .split_once('=')
+                .with_context(|| format!("Invalid device criterion '{}'", criterion))?;
+            let value = value.trim();
+
+            match key.trim() {
+                "usb" => matcher.vendor_product = Some(parse_vendor_product(value)?),
+                "name" => matcher.name = Some(value.to_string()),
+                "phys" => matcher.phys = Some(value.to_string()),
+                "has" => matcher.capabilities.push(value.parse()?),
+                key => bail!("Unknown device criterion '{}'", key),
+            }
+        }
+
+        Ok(matcher)
+    }
+}
+
+fn parse_vendor_product(value: &str) -> Result<(u16, u16)> {
+    let parse = || -> Option<(u16, u16)> {
+        let (vendor, product) = value.split_once(':')?;
+        let vendor = u16::from_str_radix(vendor, 16).ok()?;
+        let product = u16::from_str_radix(product, 16).ok()?;
+        Some((vendor, product))
+    };
+
+    parse().with_context(|| {
+        format!(
+            "Invalid USB ID '{}', expected vendor and product like '046d:c216'",
+            value
+        )
+    })
+}
+
+enum Capability {
+    Key(KeyCode),
+    Axis(AbsoluteAxisCode),
+    Led(LedCode),
+}
+
+impl Capability {
+    fn is_supported_by(&self, device: &Device) -> bool {
+        match self {
+            Self::Key(code) => device
+                .supported_keys()
+                .is_some_and(|codes| codes.contains(*code)),
+            Self::Axis(axis) => device
+                .supported_absolute_axes()
+                .is_some_and(|axes| axes.contains(*axis)),
+            Self::Led(led) => device
+                .supported_leds()
+                .is_some_and(|leds| leds.contains(*led)),
+        }
+    }
+}
+
+impl FromStr for Capability {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let name = s.to_uppercase();
+
+        if let Ok(code) = name.parse() {
+            Ok(Self::Key(code))
+        } else if let Ok(axis) = name.parse() {
+            Ok(Self::Axis(axis))
+        } else if let Ok(led) = name.parse() {
+            Ok(Self::Led(led))
+        } else {
+            bail!("Unknown key, axis, or LED '{}'", s)
+        }
+    }
+}
+
+/// Print all input devices along with their properties and
+/// capabilities.
+pub(crate) fn list_devices() -> Result<()> {
+    let key_code_name_mapping = KeyCodeNameMapping::new()?;
+
+    let mut devices: Vec<(PathBuf, Device)> = evdev::enumerate().collect();
+    devices.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    if devices.is_empty() {
+        println!("No input devices found (or none accessible).");
+        return Ok(());
+    }
+
+    for (path, device) in devices {
+        let input_id = device.input_id();
+
+        println!("{}", path.display());
+        println!("  Name: {}", device.name().unwrap_or("<unnamed>"));
+        println!(
+            "  USB ID: {:04x}:{:04x}",
+            input_id.vendor(),
+            input_id.product()
+        );
+        println!("  Phys: {}", device.physical_path().unwrap_or("<none>"));
+
+        if let Some(codes) = device.supported_keys() {
+            let names: Vec<String> = codes
+                .iter()
+                .map(
+                    |code| match key_code_name_mapping.find_name_for_code(code) {
+                        Some(name) => name.clone(),
+                        None => keycodenames::format_raw_code(code),
+                    },
+                )
+                .collect();
+            println!("  Keys: {}", names.join(", "));
+        }
+
+        if let Some(axes) = device.supported_absolute_axes() {
+            let names: Vec<String> = axes.iter().map(axes::format_axis_name).collect();
+            println!("  Axes: {}", names.join(", "));
+        }
+
+        if let Some(leds) = device.supported_leds() {
+            let names: Vec<String> = leds
+                .iter()
+                .map(|led| {
+                    let name = format!("{:?}", led);
+                    name.strip_prefix("LED_").unwrap_or(&name).to_lowercase()
+                })
+                .collect();
+            println!("  LEDs: {}", names.join(", "));
+        }
+
+        let mut matcher = format!("usb={:04x}:{:04x}", input_id.vendor(), input_id.product());
+        if let Some(phys) = device.physical_path() {
+            matcher.push_str(&format!(",phys={}", phys));
+        }
+        println!("  Matcher: {}", matcher);
+        println!();
+    }
+
+    Ok(())
+}
@@ -5,12 +5,21 @@
 
 use flume::{Receiver, SendError, Sender};
 
-use crate::buttons::Button;
-use crate::model::Tag;
+use crate::buttons::{Button, ButtonGesture, Gesture};
+use crate::devices::DeviceName;
+use crate::model::{AdminAction, Tag};
 
 pub(crate) enum Event {
-    TagRead { tag: Tag },
-    ButtonPressed { button: Button },
+    TagRead {
+        tag: Tag,
+    },
+    ButtonPressed {
+        button_gesture: ButtonGesture,
+        source: DeviceName,
+    },
+    ChordActivated {
+        action: AdminAction,
+    },
     ShutdownRequested,
 }
 
@@ -32,8 +41,21 @@ impl EventSender {
         self.send(Event::TagRead { tag })
     }
 
-    pub(crate) fn send_button_pressed(&self, button: Button) -> SendEventResult {
-        self.send(Event::ButtonPressed { button })
+    pub(crate) fn send_button_pressed(
+        &self,
+        button: Button,
+        gesture: Gesture,
+        source: DeviceName,
+    ) -> SendEventResult {
+        let button_gesture = ButtonGesture::new(button, gesture);
+        self.send(Event::ButtonPressed {
+            button_gesture,
+            source,
+        })
+    }
+
+    pub(crate) fn send_chord_activated(&self, action: AdminAction) -> SendEventResult {
+        self.send(Event::ChordActivated { action })
     }
 
     pub(crate) fn send_shutdown_requested(&self) -> SendEventResult {
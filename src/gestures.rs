@@ -0,0 +1,565 @@
+/*
+ * Copyright 2022-2026 Jochen Kupperschmidt
+ * License: MIT
+ */
+
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use flume::{Receiver, RecvTimeoutError, Sender};
+
+use crate::buttons::{Button, Gesture};
+use crate::config::ButtonsConfig;
+use crate::devices::DeviceName;
+use crate::events::EventSender;
+use crate::model::AdminAction;
+
+const DEFAULT_CHORD_WINDOW: Duration = Duration::from_millis(200);
+
+/// A button being pressed or released
+pub(crate) struct ButtonEdge {
+    pub button: Button,
+    pub pressed: bool,
+    pub time: Instant,
+    /// The device the button belongs to
+    pub source: DeviceName,
+}
+
+pub(crate) type ButtonEdgeSender = Sender<ButtonEdge>;
+pub(crate) type ButtonEdgeReceiver = Receiver<ButtonEdge>;
+
+pub(crate) fn create_button_edge_channel() -> (ButtonEdgeSender, ButtonEdgeReceiver) {
+    flume::unbounded()
+}
+
+/// Multiple buttons to be held together to trigger an action
+struct Chord {
+    buttons: HashSet<Button>,
+    hold: Duration,
+    action: AdminAction,
+}
+
+/// Buttons pressed together (within the chord window)
+struct ChordInProgress {
+    buttons: HashSet<Button>,
+    last_pressed_at: Instant,
+    /// Set once the chord has been triggered, or cancelled by releasing
+    /// one of its buttons before then
+    finished: bool,
+}
+
+#[derive(Default)]
+struct ButtonState {
+    is_held: bool,
+    /// Set while the button is held and its gesture is not yet known
+    /// (only if the gesture is determined on release)
+    pressed_at: Option<Instant>,
+    /// Set after a first press while waiting for a second one (only if
+    /// double presses are enabled)
+    first_press_at: Option<Instant>,
+    /// Whether the button is currently held for the second time
+    is_second_press: bool,
+    /// The device on which the button has last been pressed
+    source: DeviceName,
+}
+
+/// Turns button presses and releases into gestures and chords.
+///
+/// Without long and double presses enabled, a press is reported as soon
+/// as the button goes down. Enabling long presses defers reporting until
+/// the button is released or the threshold is reached. Enabling double
+/// presses defers reporting of a single press until the window for a
+/// second press has passed.
+///
+/// Presses of buttons that are part of a chord are always reported on
+/// release, and not at all if the button ends up being part of a chord.
+pub(crate) struct GestureDetector {
+    long_press_threshold: Option<Duration>,
+    double_press_window: Option<Duration>,
+    chord_window: Duration,
+    chords: Vec<Chord>,
+    chord_buttons: HashSet<Button>,
+    chord_in_progress: Option<ChordInProgress>,
+    states: HashMap<Button, ButtonState>,
+    event_sender: EventSender,
+}
+
+impl GestureDetector {
+    pub(crate) fn new(config: &ButtonsConfig, event_sender: EventSender) -> Self {
+        let chords: Vec<Chord> = config
+            .chords
+            .iter()
+            .map(|chord_config| Chord {
+                buttons: chord_config.buttons.iter().cloned().collect(),
+                hold: Duration::from_millis(chord_config.hold_in_ms),
+                action: chord_config.action,
+            })
+            .collect();
+
+        let chord_buttons = chords
+            .iter()
+            .flat_map(|chord| chord.buttons.iter().cloned())
+            .collect();
+
+        Self {
+            long_press_threshold: config.long_press_in_ms.map(Duration::from_millis),
+            double_press_window: config.double_press_window_in_ms.map(Duration::from_millis),
+            chord_window: config
+                .chord_window_in_ms
+                .map(Duration::from_millis)
+                .unwrap_or(DEFAULT_CHORD_WINDOW),
+            chords,
+            chord_buttons,
+            chord_in_progress: None,
+            states: HashMap::new(),
+            event_sender,
+        }
+    }
+
+    pub(crate) fn run(mut self, edge_receiver: ButtonEdgeReceiver) -> Result<()> {
+        loop {
+            let received = match self.find_next_deadline() {
+                Some(deadline) => edge_receiver.recv_deadline(deadline),
+                None => edge_receiver
+                    .recv()
+                    .map_err(|_| RecvTimeoutError::Disconnected),
+            };
+
+            match received {
+                Ok(edge) => self.handle_edge(edge)?,
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            }
+
+            self.handle_expired_deadlines(Instant::now())?;
+        }
+    }
+
+    fn handle_edge(&mut self, edge: ButtonEdge) -> Result<()> {
+        if edge.pressed {
+            self.handle_press(edge.button, edge.time, edge.source)
+        } else {
+            self.handle_release(edge.button, edge.time)
+        }
+    }
+
+    fn handle_press(&mut self, button: Button, time: Instant, source: DeviceName) -> Result<()> {
+        let is_chord_button = self.chord_buttons.contains(&button);
+        let defer_until_release = self.long_press_threshold.is_some() || is_chord_button;
+
+        let state = self.states.entry(button.clone()).or_default();
+        state.is_held = true;
+        state.source = source;
+
+        if is_chord_button && self.join_chord(&button, time) {
+            return Ok(());
+        }
+
+        let state = self.states.entry(button.clone()).or_default();
+        if defer_until_release {
+            state.pressed_at = Some(time);
+            state.is_second_press = state.first_press_at.take().is_some();
+            Ok(())
+        } else {
+            self.handle_short_press(button, time)
+        }
+    }
+
+    /// Add the button to the chord in progress, or start a new chord if
+    /// another chord button has been pressed recently and is still held.
+    ///
+    /// Return whether the button is now part of a chord.
+    fn join_chord(&mut self, button: &Button, time: Instant) -> bool {
+        if let Some(chord) = &mut self.chord_in_progress {
+            if time.duration_since(chord.last_pressed_at) <= self.chord_window {
+                chord.buttons.insert(button.clone());
+                chord.last_pressed_at = time;
+                return true;
+            }
+            return false;
+        }
+
+        let partners: Vec<Button> = self
+            .states
+            .iter()
+            .filter(|(other, state)| {
+                *other != button
+                    && self.chord_buttons.contains(*other)
+                    && state.is_held
+                    && state.pressed_at.is_some_and(|pressed_at| {
+                        time.duration_since(pressed_at) <= self.chord_window
+                    })
+            })
+            .map(|(other, _)| other.clone())
+            .collect();
+
+        if partners.is_empty() {
+            return false;
+        }
+
+        // The partners' presses are part of the chord, so cancel their
+        // pending gestures.
+        for partner in &partners {
+            if let Some(state) = self.states.get_mut(partner) {
+                state.pressed_at = None;
+                state.is_second_press = false;
+            }
+        }
+
+        let mut buttons: HashSet<Button> = partners.into_iter().collect();
+        buttons.insert(button.clone());
+
+        self.chord_in_progress = Some(ChordInProgress {
+            buttons,
+            last_pressed_at: time,
+            finished: false,
+        });
+
+        true
+    }
+
+    fn handle_release(&mut self, button: Button, time: Instant) -> Result<()> {
+        self.states.entry(button.clone()).or_default().is_held = false;
+
+        if let Some(chord) = &mut self.chord_in_progress
+            && chord.buttons.contains(&button)
+        {
+            // The chord's buttons have to be held until it is triggered.
+            chord.finished = true;
+
+            let all_released = chord
+                .buttons
+                .iter()
+                .all(|button| self.states.get(button).is_none_or(|state| !state.is_held));
+            if all_released {
+                self.chord_in_progress = None;
+            }
+            return Ok(());
+        }
+
+        let state = self.states.entry(button.clone()).or_default();
+        if state.pressed_at.take().is_some() {
+            // Released before the long press threshold was reached.
+            if state.is_second_press {
+                state.is_second_press = false;
+                self.emit(button, Gesture::DoublePress)
+            } else {
+                self.handle_short_press(button, time)
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    fn handle_short_press(&mut self, button: Button, time: Instant) -> Result<()> {
+        if self.double_press_window.is_none() {
+            return self.emit(button, Gesture::Press);
+        }
+
+        let state = self.states.entry(button.clone()).or_default();
+        if state.first_press_at.take().is_some() {
+            self.emit(button, Gesture::DoublePress)
+        } else {
+            state.first_press_at = Some(time);
+            Ok(())
+        }
+    }
+
+    fn handle_expired_deadlines(&mut self, now: Instant) -> Result<()> {
+        let mut gestures: Vec<(Button, Gesture)> = Vec::new();
+
+        for (button, state) in self.states.iter_mut() {
+            if let (Some(threshold), Some(pressed_at)) =
+                (self.long_press_threshold, state.pressed_at)
+                && now >= pressed_at + threshold
+            {
+                state.pressed_at = None;
+                if state.is_second_press {
+                    // Report the first press which preceded this one.
+                    state.is_second_press = false;
+                    gestures.push((button.clone(), Gesture::Press));
+                }
+                gestures.push((button.clone(), Gesture::LongPress));
+            }
+
+            if let (Some(window), Some(first_press_at)) =
+                (self.double_press_window, state.first_press_at)
+                && now >= first_press_at + window
+            {
+                state.first_press_at = None;
+                gestures.push((button.clone(), Gesture::Press));
+            }
+        }
+
+        for (button, gesture) in gestures {
+            self.emit(button, gesture)?;
+        }
+
+        if let Some(deadline) = self.find_chord_deadline()
+            && now >= deadline
+        {
+            self.trigger_chord()?;
+        }
+
+        Ok(())
+    }
+
+    fn find_matching_chord(&self) -> Option<&Chord> {
+        let chord_in_progress = self.chord_in_progress.as_ref()?;
+        if chord_in_progress.finished {
+            return None;
+        }
+
+        self.chords
+            .iter()
+            .find(|chord| chord.buttons == chord_in_progress.buttons)
+    }
+
+    fn find_chord_deadline(&self) -> Option<Instant> {
+        let chord_in_progress = self.chord_in_progress.as_ref()?;
+        self.find_matching_chord()
+            .map(|chord| chord_in_progress.last_pressed_at + chord.hold)
+    }
+
+    fn trigger_chord(&mut self) -> Result<()> {
+        let Some(action) = self.find_matching_chord().map(|chord| chord.action) else {
+            return Ok(());
+        };
+
+        if let Some(chord_in_progress) = &mut self.chord_in_progress {
+            chord_in_progress.finished = true;
+            log::debug!(
+                "Chord detected: {:?} -> {:?}",
+                chord_in_progress.buttons,
+                action
+            );
+        }
+
+        self.event_sender.send_chord_activated(action)?;
+        Ok(())
+    }
+
+    fn find_next_deadline(&self) -> Option<Instant> {
+        self.states
+            .values()
+            .flat_map(|state| {
+                [
+                    self.long_press_threshold
+                        .zip(state.pressed_at)
+                        .map(|(threshold, pressed_at)| pressed_at + threshold),
+                    self.double_press_window
+                        .zip(state.first_press_at)
+                        .map(|(window, first_press_at)| first_press_at + window),
+                ]
+            })
+            .flatten()
+            .chain(self.find_chord_deadline())
+            .min()
+    }
+
+    fn emit(&self, button: Button, gesture: Gesture) -> Result<()> {
+        let source = self
+            .states
+            .get(&button)
+            .map(|state| state.source.clone())
+            .unwrap_or_default();
+        self.event_sender
+            .send_button_pressed(button, gesture, source)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::config::ChordConfig;
+    use crate::events::{self, Event, EventReceiver};
+
+    fn create_detector(config: &ButtonsConfig) -> (GestureDetector, EventReceiver) {
+        let (event_sender, event_receiver) = events::create_event_channel();
+        (GestureDetector::new(config, event_sender), event_receiver)
+    }
+
+    fn edge(button: &str, pressed: bool, time: Instant) -> ButtonEdge {
+        ButtonEdge {
+            button: Button::from_str(button).unwrap(),
+            pressed,
+            time,
+            source: "test".to_string(),
+        }
+    }
+
+    fn press(detector: &mut GestureDetector, button: &str, time: Instant) {
+        detector.handle_edge(edge(button, true, time)).unwrap();
+        detector.handle_expired_deadlines(time).unwrap();
+    }
+
+    fn release(detector: &mut GestureDetector, button: &str, time: Instant) {
+        detector.handle_edge(edge(button, false, time)).unwrap();
+        detector.handle_expired_deadlines(time).unwrap();
+    }
+
+    fn take_events(event_receiver: &EventReceiver) -> Vec<String> {
+        event_receiver
+            .drain()
+            .map(|event| match event {
+                Event::ButtonPressed { button_gesture, .. } => button_gesture.to_string(),
+                Event::ChordActivated { action } => format!("{:?}", action),
+                Event::TagRead { .. } => "tag read".to_string(),
+                Event::ShutdownRequested => "shutdown requested".to_string(),
+            })
+            .collect()
+    }
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn chord_config() -> ButtonsConfig {
+        ButtonsConfig {
+            chords: vec![ChordConfig {
+                buttons: vec![
+                    Button::from_str("button1").unwrap(),
+                    Button::from_str("button4").unwrap(),
+                ],
+                hold_in_ms: 3000,
+                action: AdminAction::EnterAdminMode,
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn press_is_reported_immediately() {
+        let (mut detector, events) = create_detector(&ButtonsConfig::default());
+        let t0 = Instant::now();
+
+        press(&mut detector, "button1", t0);
+        assert_eq!(take_events(&events), ["button1"]);
+
+        release(&mut detector, "button1", t0 + ms(100));
+        assert!(take_events(&events).is_empty());
+    }
+
+    #[test]
+    fn short_press_is_reported_on_release_if_long_presses_are_enabled() {
+        let config = ButtonsConfig {
+            long_press_in_ms: Some(800),
+            ..Default::default()
+        };
+        let (mut detector, events) = create_detector(&config);
+        let t0 = Instant::now();
+
+        press(&mut detector, "button1", t0);
+        assert!(take_events(&events).is_empty());
+
+        release(&mut detector, "button1", t0 + ms(100));
+        assert_eq!(take_events(&events), ["button1"]);
+
+        detector.handle_expired_deadlines(t0 + ms(1000)).unwrap();
+        assert!(take_events(&events).is_empty());
+    }
+
+    #[test]
+    fn long_press_is_reported_when_threshold_is_reached() {
+        let config = ButtonsConfig {
+            long_press_in_ms: Some(800),
+            ..Default::default()
+        };
+        let (mut detector, events) = create_detector(&config);
+        let t0 = Instant::now();
+
+        press(&mut detector, "button1", t0);
+        assert_eq!(detector.find_next_deadline(), Some(t0 + ms(800)));
+
+        detector.handle_expired_deadlines(t0 + ms(800)).unwrap();
+        assert_eq!(take_events(&events), ["button1 (long)"]);
+
+        release(&mut detector, "button1", t0 + ms(1500));
+        assert!(take_events(&events).is_empty());
+    }
+
+    #[test]
+    fn double_press_is_reported() {
+        let config = ButtonsConfig {
+            double_press_window_in_ms: Some(300),
+            ..Default::default()
+        };
+        let (mut detector, events) = create_detector(&config);
+        let t0 = Instant::now();
+
+        press(&mut detector, "button1", t0);
+        release(&mut detector, "button1", t0 + ms(50));
+        press(&mut detector, "button1", t0 + ms(150));
+        release(&mut detector, "button1", t0 + ms(200));
+        assert_eq!(take_events(&events), ["button1 (double)"]);
+
+        detector.handle_expired_deadlines(t0 + ms(1000)).unwrap();
+        assert!(take_events(&events).is_empty());
+    }
+
+    #[test]
+    fn single_press_is_reported_after_double_press_window() {
+        let config = ButtonsConfig {
+            double_press_window_in_ms: Some(300),
+            ..Default::default()
+        };
+        let (mut detector, events) = create_detector(&config);
+        let t0 = Instant::now();
+
+        press(&mut detector, "button1", t0);
+        release(&mut detector, "button1", t0 + ms(50));
+        assert!(take_events(&events).is_empty());
+
+        detector.handle_expired_deadlines(t0 + ms(300)).unwrap();
+        assert_eq!(take_events(&events), ["button1"]);
+    }
+
+    #[test]
+    fn chord_is_triggered_when_held() {
+        let (mut detector, events) = create_detector(&chord_config());
+        let t0 = Instant::now();
+
+        press(&mut detector, "button1", t0);
+        press(&mut detector, "button4", t0 + ms(50));
+        assert_eq!(detector.find_next_deadline(), Some(t0 + ms(3050)));
+
+        detector.handle_expired_deadlines(t0 + ms(3050)).unwrap();
+        assert_eq!(take_events(&events), ["EnterAdminMode"]);
+
+        // Releasing the buttons does not report presses.
+        release(&mut detector, "button1", t0 + ms(3500));
+        release(&mut detector, "button4", t0 + ms(3600));
+        assert!(take_events(&events).is_empty());
+    }
+
+    #[test]
+    fn chord_is_cancelled_if_a_button_is_released_early() {
+        let (mut detector, events) = create_detector(&chord_config());
+        let t0 = Instant::now();
+
+        press(&mut detector, "button1", t0);
+        press(&mut detector, "button4", t0 + ms(50));
+        release(&mut detector, "button4", t0 + ms(1000));
+        assert_eq!(detector.find_next_deadline(), None);
+
+        detector.handle_expired_deadlines(t0 + ms(3050)).unwrap();
+        release(&mut detector, "button1", t0 + ms(4000));
+        assert!(take_events(&events).is_empty());
+    }
+
+    #[test]
+    fn chord_button_pressed_alone_is_reported_on_release() {
+        let (mut detector, events) = create_detector(&chord_config());
+        let t0 = Instant::now();
+
+        press(&mut detector, "button1", t0);
+        assert!(take_events(&events).is_empty());
+
+        release(&mut detector, "button1", t0 + ms(100));
+        assert_eq!(take_events(&events), ["button1"]);
+    }
+}
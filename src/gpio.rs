@@ -0,0 +1,458 @@
+/*
+ * Copyright 2022-2026 Jochen Kupperschmidt
+ * License: MIT
+ */
+
+//! Minimal access to GPIO lines via the Linux GPIO character device
+//! (`/dev/gpiochip*`), using version 2 of its userspace API.
+//!
+//! See `/usr/include/linux/gpio.h` (or
+//! https://github.com/torvalds/linux/blob/master/include/uapi/linux/gpio.h
+//! on the web) for details.
+
+use std::fs::{File, OpenOptions};
+use std::io::{Error, Read};
+use std::os::fd::{AsRawFd, FromRawFd};
+use std::path::Path;
+
+use anyhow::{Context, Result, ensure};
+use serde::Deserialize;
+
+const GPIO_MAX_NAME_SIZE: usize = 32;
+const GPIO_V2_LINES_MAX: usize = 64;
+const GPIO_V2_LINE_NUM_ATTRS_MAX: usize = 10;
+
+const GPIO_V2_LINE_FLAG_ACTIVE_LOW: u64 = 1 << 1;
+const GPIO_V2_LINE_FLAG_INPUT: u64 = 1 << 2;
+const GPIO_V2_LINE_FLAG_OUTPUT: u64 = 1 << 3;
+const GPIO_V2_LINE_FLAG_EDGE_RISING: u64 = 1 << 4;
+const GPIO_V2_LINE_FLAG_EDGE_FALLING: u64 = 1 << 5;
+const GPIO_V2_LINE_FLAG_BIAS_PULL_UP: u64 = 1 << 8;
+const GPIO_V2_LINE_FLAG_BIAS_PULL_DOWN: u64 = 1 << 9;
+const GPIO_V2_LINE_FLAG_BIAS_DISABLED: u64 = 1 << 10;
+
+const GPIO_V2_LINE_EVENT_RISING_EDGE: u32 = 1;
+
+// `_IOWR(0xB4, 0x07, struct gpio_v2_line_request)`
+const GPIO_V2_GET_LINE_IOCTL: libc::c_ulong = 0xC250_B407;
+// `_IOWR(0xB4, 0x0F, struct gpio_v2_line_values)`
+const GPIO_V2_LINE_SET_VALUES_IOCTL: libc::c_ulong = 0xC010_B40F;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct GpioV2LineAttribute {
+    id: u32,
+    padding: u32,
+    value: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct GpioV2LineConfigAttribute {
+    attr: GpioV2LineAttribute,
+    mask: u64,
+}
+
+#[repr(C)]
+struct GpioV2LineConfig {
+    flags: u64,
+    num_attrs: u32,
+    padding: [u32; 5],
+    attrs: [GpioV2LineConfigAttribute; GPIO_V2_LINE_NUM_ATTRS_MAX],
+}
+
+#[repr(C)]
+struct GpioV2LineRequest {
+    offsets: [u32; GPIO_V2_LINES_MAX],
+    consumer: [u8; GPIO_MAX_NAME_SIZE],
+    config: GpioV2LineConfig,
+    num_lines: u32,
+    event_buffer_size: u32,
+    padding: [u32; 5],
+    fd: i32,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct GpioV2LineEvent {
+    timestamp_ns: u64,
+    id: u32,
+    offset: u32,
+    seqno: u32,
+    line_seqno: u32,
+    padding: [u32; 6],
+}
+
+#[repr(C)]
+struct GpioV2LineValues {
+    bits: u64,
+    mask: u64,
+}
+
+const _: () = assert!(size_of::<GpioV2LineRequest>() == 592);
+const _: () = assert!(size_of::<GpioV2LineEvent>() == 48);
+
+pub(crate) type LineOffset = u32;
+
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Bias {
+    #[default]
+    PullUp,
+    PullDown,
+    Disabled,
+    AsIs,
+}
+
+impl Bias {
+    fn get_flags(self) -> u64 {
+        match self {
+            Bias::PullUp => GPIO_V2_LINE_FLAG_BIAS_PULL_UP,
+            Bias::PullDown => GPIO_V2_LINE_FLAG_BIAS_PULL_DOWN,
+            Bias::Disabled => GPIO_V2_LINE_FLAG_BIAS_DISABLED,
+            Bias::AsIs => 0,
+        }
+    }
+}
+
+/// A change of an input line's (logical) value
+pub(crate) struct LineEdge {
+    pub offset: LineOffset,
+    pub active: bool,
+}
+
+/// Input lines requested from a GPIO chip, reporting edges on both
+/// rising and falling values
+pub(crate) struct InputLines {
+    file: File,
+}
+
+impl InputLines {
+    pub(crate) fn request(
+        chip_path: &Path,
+        offsets: &[LineOffset],
+        bias: Bias,
+        active_low: bool,
+    ) -> Result<Self> {
+        let flags = get_input_flags(bias, active_low);
+        let file = request_lines(chip_path, offsets, flags)?;
+
+        Ok(Self { file })
+    }
+
+    /// Block until the next edge occurs.
+    pub(crate) fn read_edge(&mut self) -> Result<LineEdge> {
+        let mut event = GpioV2LineEvent::default();
+
+        // SAFETY: The event is plain old data and the kernel writes a
+        // complete event per read.
+        let buffer = unsafe {
+            std::slice::from_raw_parts_mut(
+                (&mut event as *mut GpioV2LineEvent).cast::<u8>(),
+                size_of::<GpioV2LineEvent>(),
+            )
+        };
+        self.file.read_exact(buffer)?;
+
+        Ok(LineEdge {
+            offset: event.offset,
+            active: event.id == GPIO_V2_LINE_EVENT_RISING_EDGE,
+        })
+    }
+}
+
+/// A single output line requested from a GPIO chip
+pub(crate) struct OutputLine {
+    file: File,
+}
+
+impl OutputLine {
+    pub(crate) fn request(chip_path: &Path, offset: LineOffset, active_low: bool) -> Result<Self> {
+        let flags = get_output_flags(active_low);
+        let file = request_lines(chip_path, &[offset], flags)?;
+
+        Ok(Self { file })
+    }
+
+    /// Set the line's (logical) value.
+    pub(crate) fn set_active(&self, active: bool) -> Result<()> {
+        let mut values = GpioV2LineValues {
+            bits: u64::from(active),
+            mask: 1,
+        };
+
+        // SAFETY: The values have the layout the ioctl expects and
+        // outlive the call.
+        let result = unsafe {
+            libc::ioctl(
+                self.file.as_raw_fd(),
+                GPIO_V2_LINE_SET_VALUES_IOCTL,
+                &mut values as *mut GpioV2LineValues,
+            )
+        };
+        if result < 0 {
+            return Err(Error::last_os_error()).context("Could not set GPIO line value");
+        }
+
+        Ok(())
+    }
+}
+
+/// Return the flags for input lines reporting edges on both rising and
+/// falling values.
+fn get_input_flags(bias: Bias, active_low: bool) -> u64 {
+    let mut flags = GPIO_V2_LINE_FLAG_INPUT
+        | GPIO_V2_LINE_FLAG_EDGE_RISING
+        | GPIO_V2_LINE_FLAG_EDGE_FALLING
+        | bias.get_flags();
+    if active_low {
+        flags |= GPIO_V2_LINE_FLAG_ACTIVE_LOW;
+    }
+    flags
+}
+
+fn get_output_flags(active_low: bool) -> u64 {
+    let mut flags = GPIO_V2_LINE_FLAG_OUTPUT;
+    if active_low {
+        flags |= GPIO_V2_LINE_FLAG_ACTIVE_LOW;
+    }
+    flags
+}
+
+fn build_line_request(offsets: &[LineOffset], flags: u64) -> Result<GpioV2LineRequest> {
+    ensure!(!offsets.is_empty(), "No GPIO lines specified.");
+    ensure!(
+        offsets.len() <= GPIO_V2_LINES_MAX,
+        "Too many GPIO lines specified (maximum: {}).",
+        GPIO_V2_LINES_MAX
+    );
+    for (index, offset) in offsets.iter().enumerate() {
+        ensure!(
+            !offsets[..index].contains(offset),
+            "GPIO line {} specified more than once.",
+            offset
+        );
+    }
+
+    // SAFETY: The request is plain old data, for which all zeros is a
+    // valid value.
+    let mut request: GpioV2LineRequest = unsafe { std::mem::zeroed() };
+    request.offsets[..offsets.len()].copy_from_slice(offsets);
+    let consumer = env!("CARGO_PKG_NAME").as_bytes();
+    request.consumer[..consumer.len()].copy_from_slice(consumer);
+    request.config.flags = flags;
+    request.num_lines = offsets.len() as u32;
+
+    Ok(request)
+}
+
+fn request_lines(chip_path: &Path, offsets: &[LineOffset], flags: u64) -> Result<File> {
+    let mut request = build_line_request(offsets, flags)?;
+
+    let chip = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(chip_path)
+        .with_context(|| format!("Could not open GPIO chip {}", chip_path.display()))?;
+
+    // SAFETY: The request has the layout the ioctl expects and outlives
+    // the call.
+    let result = unsafe {
+        libc::ioctl(
+            chip.as_raw_fd(),
+            GPIO_V2_GET_LINE_IOCTL,
+            &mut request as *mut GpioV2LineRequest,
+        )
+    };
+    if result < 0 {
+        return Err(Error::last_os_error()).with_context(|| {
+            format!(
+                "Could not request GPIO lines {:?} from {}",
+                offsets,
+                chip_path.display()
+            )
+        });
+    }
+
+    // SAFETY: The kernel returned a new file descriptor which we now
+    // exclusively own.
+    Ok(unsafe { File::from_raw_fd(request.fd) })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process;
+
+    use super::*;
+
+    #[test]
+    fn input_flags_request_edges_bias_and_polarity() {
+        let edge_flags = GPIO_V2_LINE_FLAG_INPUT
+            | GPIO_V2_LINE_FLAG_EDGE_RISING
+            | GPIO_V2_LINE_FLAG_EDGE_FALLING;
+
+        assert_eq!(
+            get_input_flags(Bias::PullUp, true),
+            edge_flags | GPIO_V2_LINE_FLAG_BIAS_PULL_UP | GPIO_V2_LINE_FLAG_ACTIVE_LOW
+        );
+        assert_eq!(
+            get_input_flags(Bias::PullDown, false),
+            edge_flags | GPIO_V2_LINE_FLAG_BIAS_PULL_DOWN
+        );
+        assert_eq!(
+            get_input_flags(Bias::Disabled, false),
+            edge_flags | GPIO_V2_LINE_FLAG_BIAS_DISABLED
+        );
+        assert_eq!(get_input_flags(Bias::AsIs, false), edge_flags);
+    }
+
+    #[test]
+    fn output_flags_request_polarity() {
+        assert_eq!(get_output_flags(false), GPIO_V2_LINE_FLAG_OUTPUT);
+        assert_eq!(
+            get_output_flags(true),
+            GPIO_V2_LINE_FLAG_OUTPUT | GPIO_V2_LINE_FLAG_ACTIVE_LOW
+        );
+    }
+
+    #[test]
+    fn line_request_contains_offsets_flags_and_consumer() {
+        let request = build_line_request(&[17, 27, 22], GPIO_V2_LINE_FLAG_INPUT).unwrap();
+
+        assert_eq!(request.num_lines, 3);
+        assert_eq!(&request.offsets[..4], &[17, 27, 22, 0]);
+        assert_eq!(request.config.flags, GPIO_V2_LINE_FLAG_INPUT);
+        assert_eq!(request.config.num_attrs, 0);
+        assert!(request.consumer.starts_with(b"verbleiber\0"));
+    }
+
+    #[test]
+    fn line_request_with_invalid_offsets_is_rejected() {
+        assert!(build_line_request(&[], 0).is_err());
+        assert!(build_line_request(&[17, 27, 17], 0).is_err());
+
+        let too_many_offsets: Vec<LineOffset> = (0..=GPIO_V2_LINES_MAX as u32).collect();
+        assert!(build_line_request(&too_many_offsets, 0).is_err());
+    }
+
+    const GPIO_SIM_CONFIG_PATH: &str = "/sys/kernel/config/gpio-sim";
+
+    /// Tests against a simulated chip need the gpio-sim kernel module to
+    /// be loaded, configfs to be mounted, and root privileges.
+    fn is_gpio_sim_available() -> bool {
+        let is_available = Path::new(GPIO_SIM_CONFIG_PATH).is_dir();
+        if !is_available {
+            eprintln!("gpio-sim is not available, skipping test.");
+        }
+        is_available
+    }
+
+    /// A chip simulated via gpio-sim, removed when dropped
+    struct SimulatedChip {
+        config_path: PathBuf,
+        dev_name: String,
+        chip_name: String,
+    }
+
+    impl SimulatedChip {
+        fn create(name: &str, line_count: u32) -> Result<Self> {
+            let config_path = Path::new(GPIO_SIM_CONFIG_PATH).join(format!(
+                "verbleiber-test-{}-{}",
+                name,
+                process::id()
+            ));
+            fs::create_dir(&config_path)?;
+
+            let mut chip = Self {
+                config_path,
+                dev_name: String::new(),
+                chip_name: String::new(),
+            };
+
+            let bank_path = chip.config_path.join("bank0");
+            fs::create_dir(&bank_path)?;
+            fs::write(bank_path.join("num_lines"), line_count.to_string())?;
+            fs::write(chip.config_path.join("live"), "1")?;
+
+            chip.dev_name = read_attribute(&chip.config_path.join("dev_name"))?;
+            chip.chip_name = read_attribute(&bank_path.join("chip_name"))?;
+
+            Ok(chip)
+        }
+
+        fn get_device_path(&self) -> PathBuf {
+            Path::new("/dev").join(&self.chip_name)
+        }
+
+        fn get_line_path(&self, offset: LineOffset) -> PathBuf {
+            Path::new("/sys/devices/platform")
+                .join(&self.dev_name)
+                .join(&self.chip_name)
+                .join(format!("sim_gpio{}", offset))
+        }
+
+        /// Pull the input line up (to drive it high) or down.
+        fn set_pull(&self, offset: LineOffset, up: bool) -> Result<()> {
+            let pull = if up { "pull-up" } else { "pull-down" };
+            fs::write(self.get_line_path(offset).join("pull"), pull)?;
+            Ok(())
+        }
+
+        /// Return the physical value of the line.
+        fn get_value(&self, offset: LineOffset) -> Result<String> {
+            read_attribute(&self.get_line_path(offset).join("value"))
+        }
+    }
+
+    impl Drop for SimulatedChip {
+        fn drop(&mut self) {
+            let _ = fs::write(self.config_path.join("live"), "0");
+            let _ = fs::remove_dir(self.config_path.join("bank0"));
+            let _ = fs::remove_dir(&self.config_path);
+        }
+    }
+
+    fn read_attribute(path: &Path) -> Result<String> {
+        Ok(fs::read_to_string(path)?.trim().to_string())
+    }
+
+    #[test]
+    fn input_lines_report_edges_of_simulated_chip() {
+        if !is_gpio_sim_available() {
+            return;
+        }
+        let chip = SimulatedChip::create("input", 4).unwrap();
+
+        let mut lines =
+            InputLines::request(&chip.get_device_path(), &[1, 2], Bias::AsIs, false).unwrap();
+
+        chip.set_pull(2, true).unwrap();
+        let edge = lines.read_edge().unwrap();
+        assert_eq!((edge.offset, edge.active), (2, true));
+
+        chip.set_pull(2, false).unwrap();
+        let edge = lines.read_edge().unwrap();
+        assert_eq!((edge.offset, edge.active), (2, false));
+    }
+
+    #[test]
+    fn output_line_sets_value_of_simulated_chip() {
+        if !is_gpio_sim_available() {
+            return;
+        }
+        let chip = SimulatedChip::create("output", 4).unwrap();
+
+        let line = OutputLine::request(&chip.get_device_path(), 3, false).unwrap();
+        line.set_active(true).unwrap();
+        assert_eq!(chip.get_value(3).unwrap(), "1");
+        line.set_active(false).unwrap();
+        assert_eq!(chip.get_value(3).unwrap(), "0");
+        drop(line);
+
+        let line = OutputLine::request(&chip.get_device_path(), 3, true).unwrap();
+        line.set_active(true).unwrap();
+        assert_eq!(chip.get_value(3).unwrap(), "0");
+    }
+}
@@ -9,6 +9,13 @@ use evdev::KeyCode;
 
 pub(crate) type KeyName = String;
 
+/// Prefix of key names that specify a key code numerically (e.g.
+/// `code:304`)
+const RAW_CODE_PREFIX: &str = "code:";
+
+/// Highest key code (`KEY_MAX`)
+const MAX_KEY_CODE: u16 = 0x2ff;
+
 pub(crate) struct KeyCodeNameMapping {
     names_to_codes: BiMap<KeyName, KeyCode>,
 }
@@ -90,14 +97,36 @@ impl KeyCodeNameMapping {
         insert("trigger_happy7", KeyCode::BTN_TRIGGER_HAPPY7)?;
         insert("trigger_happy8", KeyCode::BTN_TRIGGER_HAPPY8)?;
 
+        // keyboard (e.g. `key_a`, `key_enter`, `key_kp1`)
+        for code in 0..=MAX_KEY_CODE {
+            let code = KeyCode::new(code);
+            let name = format!("{:?}", code);
+            if name.starts_with("KEY_") {
+                insert(&name.to_lowercase(), code)?;
+            }
+        }
+
         Ok(Self { names_to_codes })
     }
 
-    pub(crate) fn find_code_for_name(&self, name: KeyName) -> Option<&KeyCode> {
-        self.names_to_codes.get_by_left(&name)
+    pub(crate) fn find_code_for_name(&self, name: KeyName) -> Option<KeyCode> {
+        if let Some(raw_code) = name.strip_prefix(RAW_CODE_PREFIX) {
+            return raw_code
+                .parse::<u16>()
+                .ok()
+                .filter(|code| *code <= MAX_KEY_CODE)
+                .map(KeyCode::new);
+        }
+
+        self.names_to_codes.get_by_left(&name).copied()
     }
 
     pub(crate) fn find_name_for_code(&self, code: KeyCode) -> Option<&KeyName> {
         self.names_to_codes.get_by_right(&code)
     }
 }
+
+/// Return the numerical notation of the key code (e.g. `code:304`).
+pub(crate) fn format_raw_code(code: KeyCode) -> KeyName {
+    format!("{}{}", RAW_CODE_PREFIX, code.code())
+}
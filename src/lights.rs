@@ -0,0 +1,256 @@
+/*
+ * Copyright 2022-2026 Jochen Kupperschmidt
+ * License: MIT
+ */
+
+//! Lights in (or next to) buttons to show the client's state
+//!
+//! - all lit while awaiting whereabouts after a tag has been read
+//! - the buttons mapped to admin actions lit in admin mode
+//! - all blinking while idle and the API is unreachable
+
+use std::collections::HashSet;
+use std::fs::{read_to_string, write};
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow};
+use evdev::{Device, LedCode, LedEvent};
+use flume::{Receiver, RecvTimeoutError, Sender};
+
+use crate::buttons::Button;
+use crate::config::{Config, LightConfig};
+use crate::devices;
+use crate::gpio::OutputLine;
+
+const DEFAULT_BLINK_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum LightsMode {
+    Idle,
+    AwaitingWhereabouts,
+    AdminMode,
+    Off,
+}
+
+enum LightsCommand {
+    SetMode(LightsMode),
+    SetOnline(bool),
+}
+
+/// Handle to control the lights, which are switched in a separate thread
+pub(crate) struct Lights {
+    command_sender: Option<Sender<LightsCommand>>,
+}
+
+impl Lights {
+    pub(crate) fn set_mode(&self, mode: LightsMode) {
+        self.send(LightsCommand::SetMode(mode));
+    }
+
+    /// Report whether the API is reachable.
+    pub(crate) fn set_online(&self, online: bool) {
+        self.send(LightsCommand::SetOnline(online));
+    }
+
+    fn send(&self, command: LightsCommand) {
+        if let Some(sender) = &self.command_sender
+            && sender.send(command).is_err()
+        {
+            log::warn!("Lights are not available anymore.");
+        }
+    }
+}
+
+pub(crate) fn create_lights(config: &Config) -> Result<Lights> {
+    let Some(lights_config) = &config.lights else {
+        return Ok(Lights {
+            command_sender: None,
+        });
+    };
+
+    let mut outputs = Vec::new();
+    for (button, light_config) in &lights_config.buttons {
+        let output = LightOutput::open(light_config)
+            .with_context(|| format!("Could not set up light for button {}", button))?;
+        outputs.push(ButtonLight {
+            button: button.clone(),
+            output,
+            is_lit: None,
+        });
+    }
+
+    let admin_buttons = config
+        .get_admin_settings()
+        .buttons_to_actions
+        .keys()
+        .map(|button_gesture| button_gesture.button.clone())
+        .collect();
+
+    let controller = LightsController {
+        lights: outputs,
+        admin_buttons,
+        blink_interval: lights_config
+            .blink_interval_in_ms
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_BLINK_INTERVAL),
+        mode: LightsMode::Idle,
+        online: true,
+        blink_phase_on: false,
+    };
+
+    let (command_sender, command_receiver) = flume::unbounded();
+
+    thread::spawn(move || controller.run(command_receiver));
+
+    Ok(Lights {
+        command_sender: Some(command_sender),
+    })
+}
+
+enum LightOutput {
+    Evdev {
+        device: Box<Device>,
+        led: LedCode,
+    },
+    Gpio(OutputLine),
+    Sysfs {
+        brightness_path: PathBuf,
+        max_brightness: String,
+    },
+}
+
+impl LightOutput {
+    fn open(config: &LightConfig) -> Result<Self> {
+        match config {
+            LightConfig::Evdev { device, led } => {
+                let led = parse_led_name(led)?;
+                let device = devices::open_device(device)
+                    .map_err(|e| anyhow!("Could not open LED device {}: {}", device, e))?;
+                Ok(Self::Evdev {
+                    device: Box::new(device),
+                    led,
+                })
+            }
+            LightConfig::Gpio {
+                chip,
+                line,
+                active_low,
+            } => Ok(Self::Gpio(OutputLine::request(chip, *line, *active_low)?)),
+            LightConfig::Sysfs { path } => {
+                let max_brightness_path = path.join("max_brightness");
+                let max_brightness = read_to_string(&max_brightness_path)
+                    .with_context(|| format!("Could not read {}", max_brightness_path.display()))?
+                    .trim()
+                    .to_string();
+                Ok(Self::Sysfs {
+                    brightness_path: path.join("brightness"),
+                    max_brightness,
+                })
+            }
+        }
+    }
+
+    fn set_lit(&mut self, lit: bool) -> Result<()> {
+        match self {
+            Self::Evdev { device, led } => {
+                let event = LedEvent::new(*led, i32::from(lit));
+                device.send_events(&[*event])?;
+            }
+            Self::Gpio(line) => line.set_active(lit)?,
+            Self::Sysfs {
+                brightness_path,
+                max_brightness,
+            } => {
+                let brightness = if lit { max_brightness.as_str() } else { "0" };
+                write(brightness_path, brightness)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parse an LED name like `numl` (for `LED_NUML`).
+fn parse_led_name(name: &str) -> Result<LedCode> {
+    format!("LED_{}", name.to_uppercase())
+        .parse()
+        .map_err(|_| anyhow!("Unknown LED name '{}'", name))
+}
+
+struct ButtonLight {
+    button: Button,
+    output: LightOutput,
+    /// Unknown until first switched
+    is_lit: Option<bool>,
+}
+
+struct LightsController {
+    lights: Vec<ButtonLight>,
+    admin_buttons: HashSet<Button>,
+    blink_interval: Duration,
+    mode: LightsMode,
+    online: bool,
+    blink_phase_on: bool,
+}
+
+impl LightsController {
+    fn run(mut self, command_receiver: Receiver<LightsCommand>) {
+        self.update();
+
+        loop {
+            let received = if self.is_blinking() {
+                command_receiver.recv_timeout(self.blink_interval)
+            } else {
+                command_receiver
+                    .recv()
+                    .map_err(|_| RecvTimeoutError::Disconnected)
+            };
+
+            match received {
+                Ok(LightsCommand::SetMode(mode)) => self.mode = mode,
+                Ok(LightsCommand::SetOnline(online)) => self.online = online,
+                Err(RecvTimeoutError::Timeout) => self.blink_phase_on = !self.blink_phase_on,
+                Err(RecvTimeoutError::Disconnected) => {
+                    self.mode = LightsMode::Off;
+                    self.update();
+                    return;
+                }
+            }
+
+            self.update();
+        }
+    }
+
+    fn is_blinking(&self) -> bool {
+        self.mode == LightsMode::Idle && !self.online
+    }
+
+    fn should_be_lit(&self, button: &Button) -> bool {
+        match self.mode {
+            LightsMode::Idle => self.is_blinking() && self.blink_phase_on,
+            LightsMode::AwaitingWhereabouts => true,
+            LightsMode::AdminMode => self.admin_buttons.contains(button),
+            LightsMode::Off => false,
+        }
+    }
+
+    fn update(&mut self) {
+        let states: Vec<bool> = self
+            .lights
+            .iter()
+            .map(|light| self.should_be_lit(&light.button))
+            .collect();
+
+        for (light, lit) in self.lights.iter_mut().zip(states) {
+            if light.is_lit == Some(lit) {
+                continue;
+            }
+
+            match light.output.set_lit(lit) {
+                Ok(()) => light.is_lit = Some(lit),
+                Err(e) => log::warn!("Could not switch light of button {}: {e}", light.button),
+            }
+        }
+    }
+}
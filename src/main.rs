@@ -4,28 +4,48 @@
  */
 
 use std::path::PathBuf;
+use std::time::Duration;
 
-use anyhow::{Result, bail};
+use anyhow::{Context, Result, bail};
 use simple_logger::SimpleLogger;
 
 mod api;
 mod audio;
+mod axes;
 mod buttons;
+mod buttonwizard;
 mod cli;
 mod client;
 mod config;
+mod configedit;
+mod configschema;
+mod debounce;
 mod devices;
 mod events;
+mod gestures;
+mod gpio;
 mod http;
 mod keycodenames;
+mod lights;
+mod metrics;
 mod model;
 mod random;
 mod registration;
+mod soundpacks;
+mod sounds;
+mod status;
+mod statusserver;
 mod tagreader;
+#[cfg(test)]
+mod testutil;
+mod tts;
 
 use crate::client::Client;
+use crate::config::ConfigOverride;
 use crate::events::{EventReceiver, EventSender};
 use crate::model::UserMode;
+use crate::registration::{ClientRegistrationRequest, PollingSettings};
+use crate::status::Status;
 
 fn main() -> Result<()> {
     SimpleLogger::new()
@@ -36,28 +56,109 @@ fn main() -> Result<()> {
     let cli = cli::parse_cli();
 
     match cli.command {
-        cli::Command::IdentifyButtons { device } => buttons::identify_buttons(device)?,
+        cli::Command::IdentifyButtons {
+            device,
+            config_filename,
+            button_count,
+            no_grab,
+        } => match config_filename {
+            Some(config_filename) => {
+                buttonwizard::map_buttons(device, &config_filename, button_count, !no_grab)?
+            }
+            None => buttons::identify_buttons(device, !no_grab)?,
+        },
+        cli::Command::ListDevices => devices::list_devices()?,
         cli::Command::Register {
             base_url,
             button_count,
+            config_filename,
+            write_config,
             audio_output,
             disable_tls_verification,
-        } => registration::register(
-            &base_url,
-            button_count,
-            audio_output,
-            disable_tls_verification,
+            location,
+            hostname,
+            mac_address,
+            reader_types,
+            button_names,
+            resume,
+            state_filename,
+            poll_interval_in_seconds,
+            timeout_in_seconds,
+        } => {
+            let polling = PollingSettings {
+                interval: Duration::from_secs(poll_interval_in_seconds),
+                timeout: timeout_in_seconds.map(Duration::from_secs),
+            };
+
+            let config_filename_to_write = config_filename.as_deref().filter(|_| write_config);
+
+            if resume {
+                registration::resume_registration(
+                    &state_filename,
+                    &polling,
+                    config_filename_to_write,
+                )?
+            } else {
+                let base_url = base_url.context("Base URL is required.")?;
+
+                // A configuration file that is only to be written might
+                // not exist yet, or be incomplete (e.g. only contain
+                // buttons mapped via `identify-buttons`).
+                let configured_button_names = match &config_filename {
+                    Some(config_filename) if button_count.is_none() || config_filename.exists() => {
+                        Some(config::load_button_names(config_filename, &cli.overrides)?)
+                    }
+                    _ => None,
+                };
+
+                let button_count = match (button_count, &configured_button_names) {
+                    (Some(button_count), _) => button_count,
+                    (None, Some(buttons)) => u8::try_from(buttons.len())?,
+                    (None, None) => bail!("Number of buttons is required."),
+                };
+
+                let mut request = ClientRegistrationRequest::new(button_count, audio_output);
+                request.location = location;
+                request.hostname = hostname.or(request.hostname);
+                request.mac_address = mac_address.or(request.mac_address);
+                request.reader_types = reader_types;
+                request.button_names = if button_names.is_empty() {
+                    configured_button_names.unwrap_or_default()
+                } else {
+                    button_names
+                };
+
+                registration::register(
+                    &base_url,
+                    request,
+                    disable_tls_verification,
+                    &state_filename,
+                    &polling,
+                    config_filename_to_write,
+                )?
+            }
+        }
+        cli::Command::Run { config_filename } => run(config_filename, &cli.overrides)?,
+        cli::Command::PlaySound {
+            config_filename,
+            name,
+        } => sounds::play_sound(
+            &config::load_config(&config_filename, &cli.overrides)?,
+            &name,
         )?,
-        cli::Command::Run { config_filename } => run(config_filename)?,
+        cli::Command::ListSounds { config_filename } => {
+            sounds::list_sounds(&config::load_config(&config_filename, &cli.overrides)?)?
+        }
+        cli::Command::ShowConfig { config_filename } => {
+            config::show_config(&config_filename, &cli.overrides)?
+        }
     }
 
     Ok(())
 }
 
-fn run(config_filename: PathBuf) -> Result<()> {
-    let config = config::load_config(&config_filename)?;
-
-    let admin_tags = config.get_admin_tags();
+fn run(config_filename: PathBuf, overrides: &[ConfigOverride]) -> Result<()> {
+    let config = config::load_config(&config_filename, overrides)?;
 
     let user_mode = config.get_user_mode();
     match user_mode {
@@ -67,7 +168,14 @@ fn run(config_filename: PathBuf) -> Result<()> {
         UserMode::MultiUser => log::info!("Running in multi-user mode."),
     }
 
-    let sounds_path = config.sounds_path.clone();
+    let audio_player = audio::create_audio_player(&config)?;
+
+    let lights = lights::create_lights(&config)?;
+
+    let status = Status::new(&user_mode);
+    if let Some(status_server_config) = &config.status_server {
+        statusserver::start_status_server(status_server_config, status.clone())?;
+    }
 
     let (tx1, rx): (EventSender, EventReceiver) = events::create_event_channel();
     let tx2 = tx1.clone();
@@ -77,28 +185,25 @@ fn run(config_filename: PathBuf) -> Result<()> {
     ctrlc::set_handler(move || handle_ctrl_c(&tx1)).expect("Could not set Ctrl-C handler");
 
     if let UserMode::MultiUser = user_mode {
-        match config.reader_input_device {
-            Some(device_name) => tagreader::handle_tag_reads(device_name, tx2)?,
+        match config.reader_input_device.clone() {
+            Some(device_name) => tagreader::handle_tag_reads(
+                device_name,
+                config.grab_reader_input_device,
+                tx2,
+                status.clone(),
+            )?,
             None => bail!("No reader device configured, but one is required in multi-user mode."),
         }
     }
 
-    buttons::handle_button_presses(
-        config.button_input_device,
-        config.buttons_to_key_code_names,
-        tx3,
-    )?;
-
-    let client = Client::new(
-        sounds_path,
-        user_mode,
-        admin_tags,
-        &config.api,
-        config.party,
-        rx,
-        tx4,
-    )?;
-    client.run()
+    let button_diagnostics = buttons::handle_button_presses(&config, tx3, &status)?;
+
+    let client = Client::new(audio_player, lights, status, user_mode, config, rx, tx4)?;
+    client.run()?;
+
+    button_diagnostics.log_summary();
+
+    Ok(())
 }
 
 fn handle_ctrl_c(event_sender: &EventSender) {
@@ -0,0 +1,350 @@
+/*
+ * Copyright 2022-2026 Jochen Kupperschmidt
+ * License: MIT
+ */
+
+//! Counters and histograms of the client's activity, rendered in the
+//! Prometheus text exposition format
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use crate::buttons::{ButtonDiagnostics, ButtonGesture, Gesture};
+
+/// Upper bounds (in seconds) of the API latency histogram buckets
+const API_LATENCY_BUCKETS: &[f64] = &[0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+#[derive(Clone, Copy, Eq, Ord, PartialEq, PartialOrd)]
+pub(crate) enum TagReadResult {
+    Known,
+    Unknown,
+    Error,
+}
+
+impl TagReadResult {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Known => "known",
+            Self::Unknown => "unknown",
+            Self::Error => "error",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Eq, Ord, PartialEq, PartialOrd)]
+pub(crate) enum ApiEndpoint {
+    SignOn,
+    SignOff,
+    TagDetails,
+    StatusUpdate,
+}
+
+impl ApiEndpoint {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::SignOn => "sign_on",
+            Self::SignOff => "sign_off",
+            Self::TagDetails => "tag_details",
+            Self::StatusUpdate => "status_update",
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Metrics {
+    state: Mutex<MetricsState>,
+    /// Counted by button input handling itself, as that also logs them
+    button_diagnostics: Arc<ButtonDiagnostics>,
+}
+
+#[derive(Default)]
+struct MetricsState {
+    tag_reads: BTreeMap<TagReadResult, u64>,
+    /// by button and gesture
+    button_presses: BTreeMap<(String, &'static str), u64>,
+    /// by whereabouts and whether successful
+    status_updates: BTreeMap<(String, bool), u64>,
+    api_latencies: BTreeMap<ApiEndpoint, Histogram>,
+    sound_playback_failures: u64,
+    /// by device and role
+    device_disconnects: BTreeMap<(String, String), u64>,
+}
+
+struct Histogram {
+    /// Number of observations per bucket (not cumulative)
+    bucket_counts: Vec<u64>,
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn new() -> Self {
+        Self {
+            bucket_counts: vec![0; API_LATENCY_BUCKETS.len()],
+            count: 0,
+            sum: 0.0,
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        if let Some(index) = API_LATENCY_BUCKETS
+            .iter()
+            .position(|upper_bound| value <= *upper_bound)
+        {
+            self.bucket_counts[index] += 1;
+        }
+        self.count += 1;
+        self.sum += value;
+    }
+}
+
+impl Metrics {
+    pub(crate) fn get_button_diagnostics(&self) -> Arc<ButtonDiagnostics> {
+        self.button_diagnostics.clone()
+    }
+
+    pub(crate) fn count_tag_read(&self, result: TagReadResult) {
+        *self.lock().tag_reads.entry(result).or_default() += 1;
+    }
+
+    pub(crate) fn count_button_press(&self, button_gesture: &ButtonGesture) {
+        let gesture = match button_gesture.gesture {
+            Gesture::Press => "press",
+            Gesture::LongPress => "long_press",
+            Gesture::DoublePress => "double_press",
+        };
+        let key = (button_gesture.button.to_string(), gesture);
+        *self.lock().button_presses.entry(key).or_default() += 1;
+    }
+
+    pub(crate) fn count_status_update(&self, whereabouts_name: &str, succeeded: bool) {
+        let key = (whereabouts_name.to_string(), succeeded);
+        *self.lock().status_updates.entry(key).or_default() += 1;
+    }
+
+    pub(crate) fn observe_api_latency(&self, endpoint: ApiEndpoint, latency: Duration) {
+        self.lock()
+            .api_latencies
+            .entry(endpoint)
+            .or_insert_with(Histogram::new)
+            .observe(latency.as_secs_f64());
+    }
+
+    pub(crate) fn count_sound_playback_failure(&self) {
+        self.lock().sound_playback_failures += 1;
+    }
+
+    pub(crate) fn count_device_disconnect(&self, device_name: &str, role: &str) {
+        let key = (device_name.to_string(), role.to_string());
+        *self.lock().device_disconnects.entry(key).or_default() += 1;
+    }
+
+    /// Render all metrics in the Prometheus text exposition format.
+    pub(crate) fn render(&self) -> String {
+        let state = self.lock();
+        let mut output = String::new();
+
+        write_header(
+            &mut output,
+            "verbleiber_tag_reads_total",
+            "counter",
+            "Tags read, by result of looking them up",
+        );
+        for result in [
+            TagReadResult::Known,
+            TagReadResult::Unknown,
+            TagReadResult::Error,
+        ] {
+            let count = state.tag_reads.get(&result).copied().unwrap_or_default();
+            write_sample(
+                &mut output,
+                "verbleiber_tag_reads_total",
+                &[("result", result.as_str())],
+                count,
+            );
+        }
+
+        write_header(
+            &mut output,
+            "verbleiber_button_presses_total",
+            "counter",
+            "Button presses, by button and gesture",
+        );
+        for ((button, gesture), count) in &state.button_presses {
+            write_sample(
+                &mut output,
+                "verbleiber_button_presses_total",
+                &[("button", button), ("gesture", gesture)],
+                count,
+            );
+        }
+
+        let mut button_counters: Vec<_> =
+            self.button_diagnostics.get_counters().into_iter().collect();
+        button_counters.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        write_header(
+            &mut output,
+            "verbleiber_button_repeats_ignored_total",
+            "counter",
+            "Key repeats of button input devices ignored, by button",
+        );
+        for (button, counters) in &button_counters {
+            write_sample(
+                &mut output,
+                "verbleiber_button_repeats_ignored_total",
+                &[("button", &button.to_string())],
+                counters.repeats_ignored,
+            );
+        }
+
+        write_header(
+            &mut output,
+            "verbleiber_button_bounces_ignored_total",
+            "counter",
+            "Button presses and releases ignored as contact bounce, by button",
+        );
+        for (button, counters) in &button_counters {
+            write_sample(
+                &mut output,
+                "verbleiber_button_bounces_ignored_total",
+                &[("button", &button.to_string())],
+                counters.bounces_ignored,
+            );
+        }
+
+        write_header(
+            &mut output,
+            "verbleiber_status_updates_total",
+            "counter",
+            "Whereabouts status updates, by whereabouts and result",
+        );
+        for ((whereabouts_name, succeeded), count) in &state.status_updates {
+            let result = if *succeeded { "success" } else { "error" };
+            write_sample(
+                &mut output,
+                "verbleiber_status_updates_total",
+                &[("whereabouts", whereabouts_name), ("result", result)],
+                count,
+            );
+        }
+
+        write_header(
+            &mut output,
+            "verbleiber_api_request_duration_seconds",
+            "histogram",
+            "Duration of API requests, by endpoint",
+        );
+        for (endpoint, histogram) in &state.api_latencies {
+            write_histogram(
+                &mut output,
+                "verbleiber_api_request_duration_seconds",
+                endpoint.as_str(),
+                histogram,
+            );
+        }
+
+        write_header(
+            &mut output,
+            "verbleiber_sound_playback_failures_total",
+            "counter",
+            "Sounds that could not be played",
+        );
+        write_sample(
+            &mut output,
+            "verbleiber_sound_playback_failures_total",
+            &[],
+            state.sound_playback_failures,
+        );
+
+        write_header(
+            &mut output,
+            "verbleiber_device_disconnects_total",
+            "counter",
+            "Input devices (and GPIO chips) that could not be read from anymore, by device and role",
+        );
+        for ((device_name, role), count) in &state.device_disconnects {
+            write_sample(
+                &mut output,
+                "verbleiber_device_disconnects_total",
+                &[("device", device_name), ("role", role)],
+                count,
+            );
+        }
+
+        output
+    }
+
+    fn lock(&self) -> MutexGuard<'_, MetricsState> {
+        // Counters stay usable even if a thread panicked while holding
+        // the lock.
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn write_header(output: &mut String, name: &str, metric_type: &str, help: &str) {
+    writeln!(output, "# HELP {name} {help}").unwrap();
+    writeln!(output, "# TYPE {name} {metric_type}").unwrap();
+}
+
+fn write_sample(
+    output: &mut String,
+    name: &str,
+    labels: &[(&str, &str)],
+    value: impl std::fmt::Display,
+) {
+    output.push_str(name);
+
+    if !labels.is_empty() {
+        let labels: Vec<String> = labels
+            .iter()
+            .map(|(label, value)| format!("{}=\"{}\"", label, escape_label_value(value)))
+            .collect();
+        write!(output, "{{{}}}", labels.join(",")).unwrap();
+    }
+
+    writeln!(output, " {value}").unwrap();
+}
+
+fn write_histogram(output: &mut String, name: &str, endpoint: &str, histogram: &Histogram) {
+    let bucket_name = format!("{name}_bucket");
+
+    let mut cumulative_count = 0;
+    for (upper_bound, count) in API_LATENCY_BUCKETS.iter().zip(&histogram.bucket_counts) {
+        cumulative_count += count;
+        write_sample(
+            output,
+            &bucket_name,
+            &[("endpoint", endpoint), ("le", &upper_bound.to_string())],
+            cumulative_count,
+        );
+    }
+    write_sample(
+        output,
+        &bucket_name,
+        &[("endpoint", endpoint), ("le", "+Inf")],
+        histogram.count,
+    );
+
+    write_sample(
+        output,
+        &format!("{name}_sum"),
+        &[("endpoint", endpoint)],
+        histogram.sum,
+    );
+    write_sample(
+        output,
+        &format!("{name}_count"),
+        &[("endpoint", endpoint)],
+        histogram.count,
+    );
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -3,6 +3,12 @@
  * License: MIT
  */
 
+use std::collections::{HashMap, HashSet};
+
+use serde::Deserialize;
+
+use crate::buttons::ButtonGesture;
+
 #[derive(Eq, Hash, PartialEq)]
 pub(crate) struct Tag {
     pub value: String,
@@ -23,3 +29,17 @@ pub(crate) enum CurrentUser {
     Admin,
     User(UserId),
 }
+
+pub(crate) struct AdminSettings {
+    pub tags: HashSet<Tag>,
+    pub buttons_to_actions: HashMap<ButtonGesture, AdminAction>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum AdminAction {
+    EnterAdminMode,
+    LeaveAdminMode,
+    ShutDown,
+    SwitchSoundPack,
+}
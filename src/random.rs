@@ -3,6 +3,53 @@
  * License: MIT
  */
 
-pub(crate) fn choose_random_element(elements: &[String]) -> Option<String> {
-    fastrand::choice(elements).cloned()
+/// Choose an element with a probability proportional to its weight.
+pub(crate) fn choose_weighted_element<T, F>(elements: &[T], get_weight: F) -> Option<&T>
+where
+    F: Fn(&T) -> u32,
+{
+    // Sum up in a wider type so that large weights cannot overflow.
+    let total_weight: u64 = elements
+        .iter()
+        .map(|element| u64::from(get_weight(element)))
+        .sum();
+    if total_weight == 0 {
+        return None;
+    }
+
+    let mut remaining = fastrand::u64(0..total_weight);
+    elements.iter().find(|element| {
+        let weight = u64::from(get_weight(element));
+        if remaining < weight {
+            true
+        } else {
+            remaining -= weight;
+            false
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn large_weights_do_not_overflow() {
+        let elements = [u32::MAX, u32::MAX, 1];
+
+        assert!(choose_weighted_element(&elements, |weight| *weight).is_some());
+    }
+
+    #[test]
+    fn elements_without_weight_are_not_chosen() {
+        let elements = [0, 1, 0];
+
+        for _ in 0..20 {
+            assert_eq!(
+                choose_weighted_element(&elements, |weight| *weight),
+                Some(&1)
+            );
+        }
+        assert_eq!(choose_weighted_element(&[0, 0], |weight| *weight), None);
+    }
 }
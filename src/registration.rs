@@ -3,47 +3,167 @@
  * License: MIT
  */
 
+use std::fs::{File, OpenOptions, read_dir, read_to_string, remove_file};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
 use std::thread::sleep;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use anyhow::{Result, anyhow, bail};
+use anyhow::{Context, Result, anyhow, bail};
 use serde::{Deserialize, Serialize};
+use toml_edit::value;
 use ureq::{Agent, Error};
 
+use crate::configedit::{self, get_or_insert_table};
 use crate::http::build_agent;
 
+/// Default for `api.timeout_in_seconds` if the configuration lacks it
+const DEFAULT_API_TIMEOUT_IN_SECONDS: i64 = 10;
+
+/// How often and for how long to wait for a registration to be approved
+pub(crate) struct PollingSettings {
+    pub interval: Duration,
+    pub timeout: Option<Duration>,
+}
+
+/// A registration that has been requested but not yet been approved or
+/// rejected, persisted to be able to resume waiting for it
+#[derive(Deserialize, Serialize)]
+struct PendingRegistration {
+    base_url: String,
+    tls_verify: bool,
+    client_id: String,
+    token: String,
+}
+
 pub(crate) fn register(
     base_url: &str,
-    button_count: u8,
-    audio_output: bool,
+    request: ClientRegistrationRequest,
     disable_tls_verification: bool,
+    state_filename: &Path,
+    polling: &PollingSettings,
+    config_filename_to_write: Option<&Path>,
 ) -> Result<()> {
+    if state_filename.exists() {
+        bail!(
+            "A registration is still pending (see {}). Resume it via `--resume`, or delete that file to start over.",
+            state_filename.display()
+        );
+    }
+
+    // Create the state file up front, so that the client ID and token
+    // are not lost if it cannot be created after registering.
+    let state_file = create_pending_registration_file(state_filename)?;
+
     let api_client = ClientRegistrationApiClient::new(base_url, disable_tls_verification);
 
-    let registration_response = api_client.register(button_count, audio_output)?;
-    let sleep_duration = Duration::from_secs(10);
+    let registration_response = match api_client.register(request) {
+        Ok(registration_response) => registration_response,
+        Err(e) => {
+            remove_pending_registration(state_filename)?;
+            return Err(e);
+        }
+    };
+    log::info!(
+        "Registration requested, client ID: {}",
+        registration_response.client_id
+    );
+
+    let pending = PendingRegistration {
+        base_url: base_url.to_owned(),
+        tls_verify: !disable_tls_verification,
+        client_id: registration_response.client_id,
+        token: registration_response.token,
+    };
+    if let Err(e) = save_pending_registration(state_file, state_filename, &pending) {
+        log::error!(
+            "Could not save pending registration (client ID: {}, client token: {}), waiting for approval anyway: {e}",
+            pending.client_id,
+            pending.token
+        );
+    }
+
+    await_approval(
+        &api_client,
+        &pending,
+        state_filename,
+        polling,
+        config_filename_to_write,
+    )
+}
+
+/// Resume waiting for a pending registration to be approved.
+pub(crate) fn resume_registration(
+    state_filename: &Path,
+    polling: &PollingSettings,
+    config_filename_to_write: Option<&Path>,
+) -> Result<()> {
+    let pending = load_pending_registration(state_filename)?;
+    log::info!("Resuming registration of client ID {}.", pending.client_id);
+
+    let api_client = ClientRegistrationApiClient::new(&pending.base_url, !pending.tls_verify);
+
+    await_approval(
+        &api_client,
+        &pending,
+        state_filename,
+        polling,
+        config_filename_to_write,
+    )
+}
+
+fn await_approval(
+    api_client: &ClientRegistrationApiClient,
+    pending: &PendingRegistration,
+    state_filename: &Path,
+    polling: &PollingSettings,
+    config_filename_to_write: Option<&Path>,
+) -> Result<()> {
+    let deadline = polling.timeout.map(|timeout| Instant::now() + timeout);
 
     loop {
-        let status_response =
-            api_client.get_registration_status(&registration_response.client_id)?;
+        let status_response = api_client.get_registration_status(&pending.client_id)?;
 
         match status_response.status {
             ClientRegistrationStatus::Pending => {
+                if deadline.is_some_and(|deadline| Instant::now() + polling.interval > deadline) {
+                    bail!(
+                        "Client registration has not been approved in time. Resume waiting via `--resume`."
+                    );
+                }
+
                 log::info!(
                     "Sleeping {:?} before retrying to fetch client registration status.",
-                    sleep_duration
+                    polling.interval
                 );
-                sleep(sleep_duration);
+                sleep(polling.interval);
                 continue;
             }
             ClientRegistrationStatus::Approved => {
-                log::info!(
-                    "Client registration was approved! Put this client token into your configuration file: {}",
-                    registration_response.token
-                );
+                match config_filename_to_write {
+                    Some(config_filename) => {
+                        write_api_config(
+                            config_filename,
+                            &pending.base_url,
+                            &pending.token,
+                            pending.tls_verify,
+                        )?;
+                        log::info!(
+                            "Client registration was approved! Wrote client token to {}.",
+                            config_filename.display()
+                        );
+                    }
+                    None => log::info!(
+                        "Client registration was approved! Put this client token into your configuration file: {}",
+                        pending.token
+                    ),
+                }
+                remove_pending_registration(state_filename)?;
                 break;
             }
             ClientRegistrationStatus::Rejected => {
+                remove_pending_registration(state_filename)?;
                 bail!("Client registration was rejected.");
             }
         }
@@ -52,10 +172,129 @@ pub(crate) fn register(
     Ok(())
 }
 
+fn create_pending_registration_file(state_filename: &Path) -> Result<File> {
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(state_filename)
+        .with_context(|| format!("Could not create {}", state_filename.display()))
+}
+
+fn save_pending_registration(
+    mut file: File,
+    state_filename: &Path,
+    pending: &PendingRegistration,
+) -> Result<()> {
+    file.write_all(toml::to_string(pending)?.as_bytes())
+        .and_then(|_| file.sync_all())
+        .with_context(|| format!("Could not write {}", state_filename.display()))
+}
+
+fn load_pending_registration(state_filename: &Path) -> Result<PendingRegistration> {
+    let text = read_to_string(state_filename).with_context(|| {
+        format!(
+            "Could not read pending registration from {}",
+            state_filename.display()
+        )
+    })?;
+    let pending = toml::from_str(&text)?;
+    Ok(pending)
+}
+
+fn remove_pending_registration(state_filename: &Path) -> Result<()> {
+    remove_file(state_filename)
+        .with_context(|| format!("Could not remove {}", state_filename.display()))
+}
+
+/// Write API settings to the `api` section of the configuration file,
+/// which is created from the example configuration if it does not exist.
+fn write_api_config(
+    config_filename: &Path,
+    base_url: &str,
+    client_token: &str,
+    tls_verify: bool,
+) -> Result<()> {
+    let mut document = configedit::load_document_or_template(config_filename)?;
+
+    let api = get_or_insert_table(document.as_table_mut(), "api")?;
+    api.insert("base_url", value(base_url));
+    api.insert("client_token", value(client_token));
+    api.insert("tls_verify", value(tls_verify));
+    if !api.contains_key("timeout_in_seconds") {
+        api.insert("timeout_in_seconds", value(DEFAULT_API_TIMEOUT_IN_SECONDS));
+    }
+
+    configedit::write_document(config_filename, &document)
+}
+
 #[derive(Debug, Serialize)]
 pub(crate) struct ClientRegistrationRequest {
     pub button_count: u8,
     pub audio_output: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    /// Where the client is set up (e.g. "main entrance")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    pub version: String,
+    pub os: String,
+    pub arch: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mac_address: Option<String>,
+    pub reader_types: Vec<String>,
+    pub button_names: Vec<String>,
+}
+
+impl ClientRegistrationRequest {
+    /// Create a request including details about this client's software
+    /// and system.
+    pub(crate) fn new(button_count: u8, audio_output: bool) -> Self {
+        Self {
+            button_count,
+            audio_output,
+            hostname: detect_hostname(),
+            location: None,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            mac_address: detect_mac_address(),
+            reader_types: Vec::new(),
+            button_names: Vec::new(),
+        }
+    }
+}
+
+fn detect_hostname() -> Option<String> {
+    read_to_string("/proc/sys/kernel/hostname")
+        .ok()
+        .map(|hostname| hostname.trim().to_string())
+        .filter(|hostname| !hostname.is_empty())
+}
+
+/// Return the MAC address of the first network interface, preferring
+/// physical over virtual ones.
+fn detect_mac_address() -> Option<String> {
+    let mut interfaces: Vec<(bool, String, String)> = read_dir("/sys/class/net")
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name() != "lo")
+        .filter_map(|entry| {
+            let path = entry.path();
+            let address = read_to_string(path.join("address"))
+                .ok()?
+                .trim()
+                .to_string();
+            let is_virtual = !path.join("device").exists();
+            let name = entry.file_name().into_string().ok()?;
+            Some((is_virtual, name, address))
+        })
+        .filter(|(_, _, address)| !address.is_empty() && address != "00:00:00:00:00:00")
+        .collect();
+
+    interfaces.sort();
+
+    interfaces.into_iter().next().map(|(_, _, address)| address)
 }
 
 #[derive(Debug, Deserialize)]
@@ -90,13 +329,10 @@ impl ClientRegistrationApiClient {
         }
     }
 
-    fn register(&self, button_count: u8, audio_output: bool) -> Result<ClientRegistrationResponse> {
+    fn register(&self, request: ClientRegistrationRequest) -> Result<ClientRegistrationResponse> {
         let url = format!("{}/client/register", self.base_url);
 
-        match self.agent.post(&url).send_json(ClientRegistrationRequest {
-            button_count,
-            audio_output,
-        }) {
+        match self.agent.post(&url).send_json(request) {
             Ok(mut response) => response
                 .body_mut()
                 .read_json::<ClientRegistrationResponse>()
@@ -0,0 +1,138 @@
+/*
+ * Copyright 2022-2026 Jochen Kupperschmidt
+ * License: MIT
+ */
+
+use std::collections::HashMap;
+use std::fs::{read_dir, read_to_string};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+
+use crate::config::SoundCandidate;
+
+pub(crate) type SoundPackName = String;
+
+const MANIFEST_FILENAME: &str = "pack.toml";
+
+#[derive(Default, Deserialize)]
+struct Manifest {
+    inherits: Option<SoundPackName>,
+    #[serde(default)]
+    sounds: HashMap<String, Vec<SoundCandidate>>,
+}
+
+/// A directory of sound files, optionally with a manifest that maps
+/// sound names to files
+pub(crate) struct SoundPack {
+    pub name: SoundPackName,
+    path: PathBuf,
+    sounds: HashMap<String, Vec<SoundCandidate>>,
+}
+
+impl SoundPack {
+    /// Treat a plain directory as a sound pack without manifest, in which
+    /// a sound's file is expected to be named after the sound.
+    pub(crate) fn from_flat_directory(path: PathBuf) -> Self {
+        Self {
+            name: path.display().to_string(),
+            path,
+            sounds: HashMap::new(),
+        }
+    }
+
+    /// Return the files this pack provides for the sound, as candidates
+    /// (with the files' paths as names) to choose one from on playback.
+    ///
+    /// Sounds listed in the manifest take precedence, whether their files
+    /// exist or not. Other sounds are provided if a file named after them
+    /// exists.
+    pub(crate) fn find_candidates(&self, name: &str) -> Vec<SoundCandidate> {
+        match self.sounds.get(name) {
+            Some(candidates) => candidates
+                .iter()
+                .map(|candidate| SoundCandidate::Weighted {
+                    name: self.path.join(candidate.name()).display().to_string(),
+                    weight: candidate.weight(),
+                })
+                .collect(),
+            None => {
+                let path = self.get_conventional_path(name);
+                if path.exists() {
+                    vec![SoundCandidate::Name(path.display().to_string())]
+                } else {
+                    Vec::new()
+                }
+            }
+        }
+    }
+
+    /// Return the path at which a sound's file is expected if it is not
+    /// listed in the manifest.
+    pub(crate) fn get_conventional_path(&self, name: &str) -> PathBuf {
+        self.path.join(format!("{}.ogg", name))
+    }
+}
+
+/// Load the named sound pack and the packs it (transitively) inherits
+/// from, most specific first.
+pub(crate) fn load_sound_pack_chain(
+    packs_path: &Path,
+    name: &SoundPackName,
+) -> Result<Vec<SoundPack>> {
+    let mut chain: Vec<SoundPack> = Vec::new();
+    let mut next_name = Some(name.to_owned());
+
+    while let Some(name) = next_name {
+        if chain.iter().any(|pack| pack.name == name) {
+            bail!("Sound pack '{}' inherits from itself.", name);
+        }
+
+        let (pack, parent_name) = load_sound_pack(packs_path, name)?;
+        chain.push(pack);
+        next_name = parent_name;
+    }
+
+    Ok(chain)
+}
+
+fn load_sound_pack(
+    packs_path: &Path,
+    name: SoundPackName,
+) -> Result<(SoundPack, Option<SoundPackName>)> {
+    let path = packs_path.join(&name);
+    let manifest_path = path.join(MANIFEST_FILENAME);
+
+    let text = read_to_string(&manifest_path).with_context(|| {
+        format!(
+            "Could not read manifest of sound pack '{}' ({})",
+            name,
+            manifest_path.display()
+        )
+    })?;
+    let manifest: Manifest = toml::from_str(&text)
+        .with_context(|| format!("Invalid manifest of sound pack '{}'", name))?;
+
+    let pack = SoundPack {
+        name,
+        path,
+        sounds: manifest.sounds,
+    };
+
+    Ok((pack, manifest.inherits))
+}
+
+/// Return the names of all sound packs (i.e. subdirectories with a
+/// manifest) in alphabetical order.
+pub(crate) fn list_sound_pack_names(packs_path: &Path) -> Result<Vec<SoundPackName>> {
+    let mut names: Vec<SoundPackName> = read_dir(packs_path)?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().join(MANIFEST_FILENAME).is_file())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect();
+
+    names.sort();
+
+    Ok(names)
+}
@@ -0,0 +1,66 @@
+/*
+ * Copyright 2022-2026 Jochen Kupperschmidt
+ * License: MIT
+ */
+
+use std::collections::BTreeSet;
+
+use anyhow::Result;
+
+use crate::audio::{self, Sound, SoundLibrary, SoundVariants};
+use crate::config::{Config, SoundCandidate};
+
+pub(crate) fn play_sound(config: &Config, name: &str) -> Result<()> {
+    let audio_player = audio::create_audio_player(config)?;
+
+    println!("Playing sound '{}' ...", name);
+    audio_player.play(name)
+}
+
+pub(crate) fn list_sounds(config: &Config) -> Result<()> {
+    let sound_lib = SoundLibrary::new(config.sounds_path.clone(), config.sound_pack.clone())?;
+    let sound_variants = SoundVariants::new(config.sound_variants.clone());
+
+    let mut names: BTreeSet<String> = Sound::get_fixed_sounds()
+        .iter()
+        .map(Sound::get_name)
+        .collect();
+    names.extend(
+        config
+            .sound_variants
+            .values()
+            .chain(config.party.whereabouts_sounds.values())
+            .flatten()
+            .map(SoundCandidate::name)
+            .map(str::to_owned),
+    );
+
+    for name in names {
+        if let Some(variant_names) = sound_variants.get_variant_names(&name) {
+            println!("{}: variants {}", name, variant_names.join(", "));
+            continue;
+        }
+
+        match sound_lib.find_files(&name) {
+            Some((pack_name, paths)) => {
+                for path in paths {
+                    let state = if path.exists() { "ok" } else { "missing" };
+                    println!(
+                        "{}: {} (pack '{}', {})",
+                        name,
+                        path.display(),
+                        pack_name,
+                        state
+                    );
+                }
+            }
+            None => println!(
+                "{}: {} (missing)",
+                name,
+                sound_lib.get_conventional_path(&name).display()
+            ),
+        }
+    }
+
+    Ok(())
+}
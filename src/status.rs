@@ -0,0 +1,190 @@
+/*
+ * Copyright 2022-2026 Jochen Kupperschmidt
+ * License: MIT
+ */
+
+//! The client's current status and metrics, collected from its
+//! components to be reported via the status server
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::devices::DeviceName;
+use crate::metrics::Metrics;
+use crate::model::{CurrentUser, UserMode};
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Handle to update and report the status, shared between threads
+#[derive(Clone)]
+pub(crate) struct Status {
+    state: Arc<Mutex<StatusState>>,
+    metrics: Arc<Metrics>,
+}
+
+struct StatusState {
+    started_at: Instant,
+    signed_on: bool,
+    user_mode: UserModeName,
+    user_state: UserState,
+    last_tag_read_at: Option<SystemTime>,
+    last_api_error: Option<ApiError>,
+    devices: BTreeMap<DeviceName, DeviceStatus>,
+}
+
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum UserModeName {
+    SingleUser,
+    MultiUser,
+}
+
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum UserState {
+    Idle,
+    AwaitingWhereabouts,
+    AdminMode,
+}
+
+#[derive(Clone, Serialize)]
+struct ApiError {
+    message: String,
+    /// Unix timestamp
+    occurred_at: u64,
+}
+
+#[derive(Clone, Serialize)]
+struct DeviceStatus {
+    role: String,
+    connected: bool,
+}
+
+/// Snapshot of the status, as served as JSON
+#[derive(Serialize)]
+pub(crate) struct StatusReport {
+    version: &'static str,
+    uptime_in_seconds: u64,
+    signed_on: bool,
+    user_mode: UserModeName,
+    user_state: UserState,
+    /// Unix timestamp
+    last_tag_read_at: Option<u64>,
+    last_api_error: Option<ApiError>,
+    devices: BTreeMap<DeviceName, DeviceStatus>,
+}
+
+impl Status {
+    pub(crate) fn new(user_mode: &UserMode) -> Self {
+        let user_mode = match user_mode {
+            UserMode::SingleUser(_) => UserModeName::SingleUser,
+            UserMode::MultiUser => UserModeName::MultiUser,
+        };
+
+        Self {
+            state: Arc::new(Mutex::new(StatusState {
+                started_at: Instant::now(),
+                signed_on: false,
+                user_mode,
+                user_state: UserState::Idle,
+                last_tag_read_at: None,
+                last_api_error: None,
+                devices: BTreeMap::new(),
+            })),
+            metrics: Arc::new(Metrics::default()),
+        }
+    }
+
+    pub(crate) fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    pub(crate) fn set_signed_on(&self, signed_on: bool) {
+        self.lock().signed_on = signed_on;
+    }
+
+    pub(crate) fn set_current_user(&self, current_user: &CurrentUser) {
+        self.lock().user_state = match current_user {
+            CurrentUser::None => UserState::Idle,
+            CurrentUser::User(_) => UserState::AwaitingWhereabouts,
+            CurrentUser::Admin => UserState::AdminMode,
+        };
+    }
+
+    pub(crate) fn record_tag_read(&self) {
+        self.lock().last_tag_read_at = Some(SystemTime::now());
+    }
+
+    pub(crate) fn record_api_error(&self, error: &anyhow::Error) {
+        self.lock().last_api_error = Some(ApiError {
+            message: error.to_string(),
+            occurred_at: to_unix_timestamp(SystemTime::now()),
+        });
+    }
+
+    /// Run the handler of an input device (or GPIO chip) with the given
+    /// role (e.g. "reader input device"), reporting the device as
+    /// connected until the handler ends (e.g. because the device has
+    /// been unplugged).
+    pub(crate) fn track_device<F>(
+        &self,
+        device_name: DeviceName,
+        role: &str,
+        handle: F,
+    ) -> Result<()>
+    where
+        F: FnOnce() -> Result<()>,
+    {
+        self.set_device_connected(&device_name, role, true);
+
+        let result = handle();
+
+        self.set_device_connected(&device_name, role, false);
+        self.metrics.count_device_disconnect(&device_name, role);
+        if let Err(e) = &result {
+            log::error!("Stopped handling {role} {device_name}: {e}");
+        }
+
+        result
+    }
+
+    fn set_device_connected(&self, device_name: &DeviceName, role: &str, connected: bool) {
+        self.lock().devices.insert(
+            device_name.clone(),
+            DeviceStatus {
+                role: role.to_string(),
+                connected,
+            },
+        );
+    }
+
+    pub(crate) fn report(&self) -> StatusReport {
+        let state = self.lock();
+        StatusReport {
+            version: VERSION,
+            uptime_in_seconds: state.started_at.elapsed().as_secs(),
+            signed_on: state.signed_on,
+            user_mode: state.user_mode,
+            user_state: state.user_state,
+            last_tag_read_at: state.last_tag_read_at.map(to_unix_timestamp),
+            last_api_error: state.last_api_error.clone(),
+            devices: state.devices.clone(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, StatusState> {
+        // The state stays consistent even if a thread panicked while
+        // holding the lock, as every update is a single assignment.
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn to_unix_timestamp(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
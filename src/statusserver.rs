@@ -0,0 +1,64 @@
+/*
+ * Copyright 2022-2026 Jochen Kupperschmidt
+ * License: MIT
+ */
+
+//! Embedded HTTP server to monitor a running client
+//!
+//! - `GET /status`: status as JSON
+//! - `GET /metrics`: metrics in the Prometheus text exposition format
+
+use std::thread;
+
+use anyhow::{Result, anyhow};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::config::StatusServerConfig;
+use crate::status::Status;
+
+pub(crate) fn start_status_server(config: &StatusServerConfig, status: Status) -> Result<()> {
+    let server = Server::http(&config.address)
+        .map_err(|e| anyhow!("Could not start status server on {}: {}", config.address, e))?;
+
+    log::info!(
+        "Serving status and metrics on http://{}/status and http://{}/metrics",
+        config.address,
+        config.address
+    );
+
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            if let Err(e) = handle_request(request, &status) {
+                log::warn!("Could not respond to status request: {e}");
+            }
+        }
+    });
+
+    Ok(())
+}
+
+fn handle_request(request: Request, status: &Status) -> Result<()> {
+    if request.method() != &Method::Get {
+        return Ok(request.respond(Response::empty(405))?);
+    }
+
+    match request.url() {
+        "/status" => {
+            let body = serde_json::to_string_pretty(&status.report())?;
+            let response =
+                Response::from_string(body).with_header(content_type_header("application/json"));
+            Ok(request.respond(response)?)
+        }
+        "/metrics" => {
+            let body = status.metrics().render();
+            let response = Response::from_string(body)
+                .with_header(content_type_header("text/plain; version=0.0.4"));
+            Ok(request.respond(response)?)
+        }
+        _ => Ok(request.respond(Response::empty(404))?),
+    }
+}
+
+fn content_type_header(value: &str) -> Header {
+    Header::from_bytes("Content-Type", value).expect("header is valid")
+}
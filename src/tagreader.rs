@@ -12,18 +12,28 @@ use crate::devices;
 use crate::devices::DeviceName;
 use crate::events::EventSender;
 use crate::model::Tag;
+use crate::status::Status;
 
-pub(crate) fn handle_tag_reads(device_name: DeviceName, event_sender: EventSender) -> Result<()> {
-    let device = open_device(device_name)?;
+pub(crate) fn handle_tag_reads(
+    device_name: DeviceName,
+    grab: bool,
+    event_sender: EventSender,
+    status: Status,
+) -> Result<()> {
+    let device = open_device(device_name.clone(), grab)?;
 
     let tag_read_handler = TagReadHandler::new(event_sender);
-    thread::spawn(move || tag_read_handler.run(device));
+    thread::spawn(move || {
+        status.track_device(device_name, "reader input device", || {
+            tag_read_handler.run(device)
+        })
+    });
     Ok(())
 }
 
-fn open_device(device_name: DeviceName) -> Result<Device> {
+fn open_device(device_name: DeviceName, grab: bool) -> Result<Device> {
     let device_label = "reader input device".to_string();
-    devices::open_input_device(device_name, device_label)
+    devices::open_input_device(device_name, device_label, grab)
 }
 
 struct TagReadHandler {
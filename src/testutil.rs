@@ -0,0 +1,53 @@
+/*
+ * Copyright 2022-2026 Jochen Kupperschmidt
+ * License: MIT
+ */
+
+//! Helpers shared by tests
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// A temporary directory that is removed, along with its content, when
+/// dropped (even if the test fails)
+pub(crate) struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// Create a directory whose name includes the given one, which has
+    /// to be unique among tests, as they run in parallel.
+    pub(crate) fn new(name: &str) -> Self {
+        let path = env::temp_dir().join(format!("verbleiber-test-{}-{}", name, process::id()));
+        fs::create_dir_all(&path).unwrap();
+        Self { path }
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Write the files to the directory.
+    pub(crate) fn with_files(self, files: &[(&str, &str)]) -> Self {
+        for (filename, text) in files {
+            fs::write(self.path.join(filename), text).unwrap();
+        }
+        self
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Return whether the program can be found in the directories listed in
+/// `PATH`.
+pub(crate) fn is_program_available(name: &str) -> bool {
+    env::var_os("PATH").is_some_and(|paths| {
+        env::split_paths(&paths).any(|directory| directory.join(name).is_file())
+    })
+}
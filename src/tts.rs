@@ -0,0 +1,206 @@
+/*
+ * Copyright 2022-2026 Jochen Kupperschmidt
+ * License: MIT
+ */
+
+use std::collections::HashMap;
+use std::fs::{create_dir_all, remove_file, rename};
+use std::path::PathBuf;
+use std::process::{Child, Command, ExitStatus};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, bail, ensure};
+
+use crate::config::TtsConfig;
+
+/// How often to check whether the text-to-speech command has finished
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Something to say if there is no sound file to play instead
+pub(crate) enum Phrase {
+    Greeting { screen_name: String },
+    Whereabouts { whereabouts_name: String },
+}
+
+/// Generates speech audio files by invoking an external text-to-speech
+/// program (e.g. `espeak-ng` or `piper`) and caches them on disk.
+pub(crate) struct SpeechSynthesizer {
+    command: Vec<String>,
+    timeout: Duration,
+    cache_path: PathBuf,
+    greeting_template: String,
+    whereabouts_template: String,
+    whereabouts_labels: HashMap<String, String>,
+}
+
+impl SpeechSynthesizer {
+    pub(crate) fn new(config: &TtsConfig) -> Result<Self> {
+        ensure!(
+            !config.command.is_empty(),
+            "No text-to-speech command configured."
+        );
+
+        Ok(Self {
+            command: config.command.clone(),
+            timeout: Duration::from_secs(config.timeout_in_seconds),
+            cache_path: config.cache_path.clone(),
+            greeting_template: config.greeting_template.clone(),
+            whereabouts_template: config.whereabouts_template.clone(),
+            whereabouts_labels: config.whereabouts_labels.clone(),
+        })
+    }
+
+    pub(crate) fn render(&self, phrase: &Phrase) -> String {
+        match phrase {
+            Phrase::Greeting { screen_name } => {
+                self.greeting_template.replace("{screen_name}", screen_name)
+            }
+            Phrase::Whereabouts { whereabouts_name } => {
+                let label = self
+                    .whereabouts_labels
+                    .get(whereabouts_name)
+                    .unwrap_or(whereabouts_name);
+                self.whereabouts_template
+                    .replace("{whereabouts_name}", whereabouts_name)
+                    .replace("{whereabouts_label}", label)
+            }
+        }
+    }
+
+    /// Return the path of an audio file containing the spoken text,
+    /// generating it first unless already cached.
+    pub(crate) fn synthesize(&self, text: &str) -> Result<PathBuf> {
+        let path = self
+            .cache_path
+            .join(format!("{}.wav", build_cache_key(text)));
+        if path.exists() {
+            return Ok(path);
+        }
+
+        create_dir_all(&self.cache_path).with_context(|| {
+            format!(
+                "Could not create speech cache directory {}",
+                self.cache_path.display()
+            )
+        })?;
+
+        // Write to a temporary file first so that an interrupted run
+        // does not leave a broken file in the cache.
+        let temp_path = path.with_extension("wav.tmp");
+        let output = temp_path.to_string_lossy();
+
+        let program = &self.command[0];
+        let args = self.command[1..]
+            .iter()
+            .map(|arg| arg.replace("{text}", text).replace("{output}", &output));
+
+        let mut child = Command::new(program)
+            .args(args)
+            .spawn()
+            .with_context(|| format!("Could not run text-to-speech command '{}'", program))?;
+        let status = wait_with_timeout(&mut child, self.timeout).inspect_err(|_| {
+            // The command may have written a partial file.
+            let _ = remove_file(&temp_path);
+        });
+        let status = status
+            .with_context(|| format!("Text-to-speech command '{}' did not finish", program))?;
+        ensure!(
+            status.success(),
+            "Text-to-speech command '{}' failed: {}",
+            program,
+            status
+        );
+        ensure!(
+            temp_path.exists(),
+            "Text-to-speech command '{}' did not write {}",
+            program,
+            temp_path.display()
+        );
+
+        rename(&temp_path, &path)?;
+        log::debug!("Cached speech for \"{}\" at {}.", text, path.display());
+
+        Ok(path)
+    }
+}
+
+/// Wait for the process to exit, killing it if it does not within the
+/// timeout (e.g. because it hangs).
+fn wait_with_timeout(child: &mut Child, timeout: Duration) -> Result<ExitStatus> {
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+
+        if Instant::now() >= deadline {
+            child.kill()?;
+            child.wait()?;
+            bail!("Timed out after {:?}", timeout);
+        }
+
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Derive a filesystem-safe, stable cache key from the text.
+fn build_cache_key(text: &str) -> String {
+    let slug: String = text
+        .chars()
+        .filter_map(|ch| {
+            if ch.is_ascii_alphanumeric() {
+                Some(ch.to_ascii_lowercase())
+            } else if ch.is_whitespace() || ch == '-' || ch == '_' {
+                Some('-')
+            } else {
+                None
+            }
+        })
+        .take(48)
+        .collect();
+
+    format!("{}-{:016x}", slug, fnv1a_hash(text))
+}
+
+/// FNV-1a is used (instead of `DefaultHasher`) because its output must
+/// not change between Rust releases, or the cache would be invalidated.
+fn fnv1a_hash(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testutil::{self, TempDir};
+
+    use super::*;
+
+    #[test]
+    fn hanging_command_is_killed_after_timeout() {
+        // Without the program, the command would fail right away, and
+        // the timeout would go untested.
+        if !testutil::is_program_available("sleep") {
+            eprintln!("Program `sleep` is not available, skipping test.");
+            return;
+        }
+
+        let cache_dir = TempDir::new("tts-timeout");
+        let synthesizer = SpeechSynthesizer {
+            command: vec!["sleep".to_string(), "10".to_string()],
+            timeout: Duration::from_millis(100),
+            cache_path: cache_dir.path().to_path_buf(),
+            greeting_template: String::new(),
+            whereabouts_template: String::new(),
+            whereabouts_labels: HashMap::new(),
+        };
+
+        let started_at = Instant::now();
+        let result = synthesizer.synthesize("hello");
+
+        assert!(result.is_err());
+        assert!(started_at.elapsed() < Duration::from_secs(5));
+    }
+}